@@ -0,0 +1,252 @@
+//! Measures event emission throughput through `Context`.
+//!
+//! Each round emits `EMITS` events spread over `EVENTS` distinct named events, or a single typed
+//! event. Every event has `LISTENERS_PER_EVENT` listening controllers. `legacy` replays the
+//! original `HashMap<String, BTreeSet<ObjectId>>` registry for comparison. Despawning measures how
+//! removing listeners scales with the number of listeners of a shared event. Run with
+//! `cargo bench -p object-system`.
+
+use object_system::{
+    Context, ContextProxy, Controller, Event, EventHandler, EventId, EventMeta, EventSender,
+    ObjectId,
+};
+use std::{
+    any::Any,
+    collections::{BTreeSet, HashMap},
+    hint::black_box,
+    time::Instant,
+};
+
+const EVENTS: usize = 100;
+const LISTENERS_PER_EVENT: usize = 4;
+const EMITS: usize = 100_000;
+
+struct Tick;
+
+impl Event for Tick {}
+
+struct Counter {
+    event: String,
+    listens_to_tick: bool,
+    count: u64,
+}
+
+impl Controller for Counter {
+    fn on_ready(&mut self, object_id: ObjectId, ctx: &mut ContextProxy) {
+        ctx.listen_event(self.event.as_str(), object_id);
+
+        if self.listens_to_tick {
+            ctx.listen::<Tick, Self>(object_id);
+        }
+    }
+
+    fn on_event(
+        &mut self,
+        _event: &str,
+        _param: &dyn Any,
+        _meta: &EventMeta,
+        _object_id: ObjectId,
+        _ctx: &mut ContextProxy,
+    ) {
+        self.count += 1;
+    }
+}
+
+impl EventHandler<Tick> for Counter {
+    fn on_event(
+        &mut self,
+        _event: &Tick,
+        _meta: &EventMeta,
+        _object_id: ObjectId,
+        _ctx: &mut ContextProxy,
+    ) {
+        self.count += 1;
+    }
+}
+
+/// The named-event path `Context` used before events were interned. Emitting queued an owned
+/// name, and flushing looked every name up in the registry and every listener up in the
+/// controller map.
+#[derive(Default)]
+struct LegacyEventRegistry {
+    event_to_object_ids: HashMap<String, BTreeSet<ObjectId>>,
+    controllers: HashMap<ObjectId, Box<dyn Controller>>,
+    queue: Vec<(String, Box<dyn Any>)>,
+}
+
+impl LegacyEventRegistry {
+    fn listen(&mut self, event: String, object_id: ObjectId, controller: Box<dyn Controller>) {
+        self.event_to_object_ids
+            .entry(event)
+            .or_default()
+            .insert(object_id);
+        self.controllers.insert(object_id, controller);
+    }
+
+    fn emit_event(&mut self, event: impl Into<String>, param: impl Any) {
+        self.queue.push((event.into(), Box::new(param)));
+    }
+
+    fn flush(&mut self, ctx: &mut ContextProxy) {
+        let meta = EventMeta {
+            sender: EventSender::Host,
+            frame: 0,
+            sequence: 0,
+        };
+
+        for (event, param) in std::mem::take(&mut self.queue) {
+            if let Some(object_ids) = self.event_to_object_ids.get(&event) {
+                for object_id in object_ids {
+                    if let Some(controller) = self.controllers.get_mut(object_id) {
+                        controller.on_event(&event, &param, &meta, *object_id, ctx);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn event_name(index: usize) -> String {
+    format!("event-{index}")
+}
+
+fn setup() -> Context {
+    let mut context = Context::new();
+
+    context.with_proxy(|ctx| {
+        for index in 0..EVENTS * LISTENERS_PER_EVENT {
+            let object_id = ctx.create_object();
+            let controller = Counter {
+                event: event_name(index % EVENTS),
+                listens_to_tick: index < LISTENERS_PER_EVENT,
+                count: 0,
+            };
+            ctx.attach_controller(object_id, controller);
+        }
+    });
+
+    context
+}
+
+fn setup_legacy() -> (Context, LegacyEventRegistry) {
+    let mut context = Context::new();
+    let mut registry = LegacyEventRegistry::default();
+
+    context.with_proxy(|ctx| {
+        for index in 0..EVENTS * LISTENERS_PER_EVENT {
+            let controller = Counter {
+                event: event_name(index % EVENTS),
+                listens_to_tick: false,
+                count: 0,
+            };
+            registry.listen(
+                controller.event.clone(),
+                ctx.create_object(),
+                Box::new(controller),
+            );
+        }
+    });
+
+    (context, registry)
+}
+
+fn bench(name: &str, context: &mut Context, mut emit: impl FnMut(&mut ContextProxy, usize)) {
+    let mut round = |context: &mut Context| {
+        context.with_proxy(|ctx| {
+            for index in 0..EMITS {
+                emit(ctx, index);
+            }
+        })
+    };
+
+    // Warm up caches and the allocator before measuring, then keep the fastest round.
+    round(context);
+
+    let elapsed = (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            round(context);
+            start.elapsed()
+        })
+        .min()
+        .unwrap();
+
+    println!(
+        "{name:<40} {:>8.2} ns/emit",
+        elapsed.as_nanos() as f64 / EMITS as f64
+    );
+}
+
+/// Despawns `count` objects that all listen to one shared event. The time per despawn should not
+/// grow with `count`.
+fn bench_despawn(count: usize) {
+    let elapsed = (0..5)
+        .map(|_| {
+            let mut context = Context::new();
+            let object_ids = context.with_proxy(|ctx| {
+                (0..count)
+                    .map(|_| {
+                        let object_id = ctx.create_object();
+                        let controller = Counter {
+                            event: "shared".to_owned(),
+                            listens_to_tick: false,
+                            count: 0,
+                        };
+                        ctx.attach_controller(object_id, controller);
+                        object_id
+                    })
+                    .collect::<Vec<_>>()
+            });
+
+            let start = Instant::now();
+            context.with_proxy(|ctx| {
+                for &object_id in &object_ids {
+                    ctx.remove_object(black_box(object_id));
+                }
+            });
+            start.elapsed()
+        })
+        .min()
+        .unwrap();
+
+    println!(
+        "{:<40} {:>8.2} ns/despawn",
+        format!("despawn/{count} shared listeners"),
+        elapsed.as_nanos() as f64 / count as f64
+    );
+}
+
+fn main() {
+    let names = (0..EVENTS).map(event_name).collect::<Vec<_>>();
+    let ids = names
+        .iter()
+        .map(|name| EventId::new(name))
+        .collect::<Vec<_>>();
+    let mut context = setup();
+    let (mut legacy_context, mut legacy) = setup_legacy();
+
+    bench(
+        "emit/legacy string name",
+        &mut legacy_context,
+        |ctx, index| {
+            legacy.emit_event(black_box(names[index % EVENTS].as_str()), ());
+
+            // Flush at the end of the round, where `Context` flushes its own queue.
+            if index == EMITS - 1 {
+                legacy.flush(ctx);
+            }
+        },
+    );
+    bench("emit/string name", &mut context, |ctx, index| {
+        ctx.emit_event(black_box(names[index % EVENTS].as_str()), ());
+    });
+    bench("emit/interned event id", &mut context, |ctx, index| {
+        ctx.emit_event(black_box(ids[index % EVENTS]), ());
+    });
+    bench("emit/typed event", &mut context, |ctx, _| {
+        ctx.emit(black_box(Tick));
+    });
+
+    bench_despawn(10_000);
+    bench_despawn(100_000);
+}
@@ -21,6 +21,10 @@ impl AnyComponent {
         self.id
     }
 
+    pub(crate) fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+
     pub fn name(&self) -> &'static str {
         self.inner.name()
     }
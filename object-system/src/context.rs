@@ -1,34 +1,35 @@
 use crate::{
     storage::{ControllerStorage, ObjectStorage},
-    ContextActionItem, ContextProxy, ContextResult, EventReceiverStorage,
+    ContextActionItem, ContextProxy, ContextResult, EventMeta, EventReceiverStorage, EventSender,
 };
-use std::num::NonZeroU32;
 
 pub struct Context {
-    next_object_id: NonZeroU32,
-    next_component_id: NonZeroU32,
     object_storage: ObjectStorage,
     event_receiver_storage: EventReceiverStorage,
     controller_storage: ControllerStorage,
+    frame: u64,
+    event_sequence: u64,
 }
 
 impl Context {
     pub fn new() -> Self {
         Self {
-            next_object_id: NonZeroU32::MIN,
-            next_component_id: NonZeroU32::MIN,
             object_storage: ObjectStorage::new(),
             event_receiver_storage: EventReceiverStorage::new(),
             controller_storage: ControllerStorage::new(),
+            frame: 0,
+            event_sequence: 0,
         }
     }
 
+    /// Counts the frames proceeded so far. Events emitted from [`with_proxy`](Self::with_proxy)
+    /// between frames carry the number of the frame that follows them.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
     pub fn with_proxy<R>(&mut self, f: impl FnOnce(&mut ContextProxy) -> R) -> R {
-        let mut ctx = ContextProxy::new(
-            self.next_object_id,
-            self.next_component_id,
-            &mut self.object_storage,
-        );
+        let mut ctx = ContextProxy::new(&mut self.object_storage);
         let result = f(&mut ctx);
         let ctx_result = ctx.into_result();
         self.handle_context_result(ctx_result);
@@ -36,36 +37,37 @@ impl Context {
     }
 
     pub fn proceed_one_frame(&mut self) {
-        let mut ctx = ContextProxy::new(
-            self.next_object_id,
-            self.next_component_id,
-            &mut self.object_storage,
-        );
+        let mut ctx = ContextProxy::new(&mut self.object_storage);
         self.controller_storage.invoke_on_update(&mut ctx);
 
         let result = ctx.into_result();
         self.handle_context_result(result);
 
-        let mut ctx = ContextProxy::new(
-            self.next_object_id,
-            self.next_component_id,
-            &mut self.object_storage,
-        );
+        let mut ctx = ContextProxy::new(&mut self.object_storage);
         self.controller_storage.invoke_on_late_update(&mut ctx);
 
         let result = ctx.into_result();
         self.handle_context_result(result);
+
+        self.frame += 1;
     }
 
     fn handle_context_result(&mut self, mut result: ContextResult) {
         let mut removed_objects = vec![];
+        let frame = self.frame;
+        let event_sequence = &mut self.event_sequence;
+        let mut next_meta = |sender: EventSender| {
+            let sequence = *event_sequence;
+            *event_sequence += 1;
+            EventMeta {
+                sender,
+                frame,
+                sequence,
+            }
+        };
 
         while !result.action_queue.is_empty() {
-            let mut ctx = ContextProxy::new(
-                result.next_object_id,
-                result.next_component_id,
-                &mut self.object_storage,
-            );
+            let mut ctx = ContextProxy::new(&mut self.object_storage);
 
             for action in result.action_queue {
                 match action {
@@ -79,6 +81,11 @@ impl Context {
                         object_id,
                         controller,
                     } => {
+                        // The replaced controller's typed listens would no longer downcast.
+                        if self.controller_storage.controller_type(object_id).is_some() {
+                            self.event_receiver_storage.unlisten_all(object_id);
+                        }
+
                         self.controller_storage
                             .attach_controller(object_id, controller, &mut ctx);
                     }
@@ -99,8 +106,27 @@ impl Context {
                     ContextActionItem::UnlistenOnLateUpdate { object_id } => {
                         self.controller_storage.unlisten_on_late_update(object_id);
                     }
-                    ContextActionItem::ListenEvent { event, object_id } => {
-                        self.event_receiver_storage.listen(event, object_id);
+                    ContextActionItem::ListenEvent {
+                        event,
+                        object_id,
+                        priority,
+                        dispatcher,
+                        controller_type,
+                    } => {
+                        // A typed listen naming the wrong controller would never deliver.
+                        let attached_type = self.controller_storage.controller_type(object_id);
+                        let accepted = controller_type
+                            .zip(attached_type)
+                            .is_none_or(|(expected, attached)| expected == attached);
+                        debug_assert!(
+                            accepted,
+                            "{object_id:?} cannot listen to {event:?}, its controller has another type"
+                        );
+
+                        if accepted {
+                            self.event_receiver_storage
+                                .listen(event, object_id, priority, dispatcher);
+                        }
                     }
                     ContextActionItem::UnlistenEvent { event, object_id } => {
                         self.event_receiver_storage.unlisten(event, object_id);
@@ -108,14 +134,70 @@ impl Context {
                     ContextActionItem::UnlistenEventAll { object_id } => {
                         self.event_receiver_storage.unlisten_all(object_id);
                     }
-                    ContextActionItem::EmitEvent { event, param } => {
+                    ContextActionItem::EmitEvent {
+                        event,
+                        param,
+                        sender,
+                    } => {
+                        let meta = next_meta(sender);
                         self.event_receiver_storage.emit(
-                            &event,
-                            &param,
+                            event,
+                            param.as_ref(),
+                            &meta,
                             &mut ctx,
                             &mut self.controller_storage,
                         );
                     }
+                    ContextActionItem::EmitEventTo {
+                        event,
+                        target,
+                        param,
+                        sender,
+                        dispatcher,
+                    } => {
+                        let meta = next_meta(sender);
+                        if let Some((event_name, dispatcher)) = self
+                            .event_receiver_storage
+                            .directed_dispatcher(event, target, dispatcher)
+                        {
+                            self.controller_storage.dispatch_event(
+                                target,
+                                event_name,
+                                dispatcher,
+                                param.as_ref(),
+                                &meta,
+                                &mut ctx,
+                            );
+                        }
+                    }
+                    ContextActionItem::EmitEventToMany {
+                        event,
+                        targets,
+                        param,
+                        sender,
+                        dispatcher,
+                    } => {
+                        let meta = next_meta(sender);
+                        for target in targets {
+                            let consumed = self
+                                .event_receiver_storage
+                                .directed_dispatcher(event, target, dispatcher)
+                                .is_some_and(|(event_name, dispatcher)| {
+                                    self.controller_storage.dispatch_event(
+                                        target,
+                                        event_name,
+                                        dispatcher,
+                                        param.as_ref(),
+                                        &meta,
+                                        &mut ctx,
+                                    )
+                                });
+
+                            if consumed {
+                                break;
+                            }
+                        }
+                    }
                 }
             }
 
@@ -125,8 +207,482 @@ impl Context {
         for object_id in removed_objects {
             self.object_storage.remove(object_id);
         }
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Controller, Event, EventHandler, EventId, EventMeta, EventSender, ObjectId};
+    use std::{any::Any, cell::RefCell, rc::Rc};
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    struct Ping(u32);
+
+    impl Event for Ping {}
+
+    struct Pong;
+
+    impl Event for Pong {}
+
+    struct Listener {
+        log: Log,
+    }
+
+    impl Controller for Listener {
+        fn on_ready(&mut self, object_id: ObjectId, ctx: &mut ContextProxy) {
+            ctx.listen::<Ping, Self>(object_id);
+            ctx.listen_event("ping", object_id);
+        }
+
+        fn on_event(
+            &mut self,
+            event: &str,
+            param: &dyn Any,
+            _meta: &EventMeta,
+            _object_id: ObjectId,
+            _ctx: &mut ContextProxy,
+        ) {
+            let param = param.downcast_ref::<u32>().copied();
+            self.log.borrow_mut().push(format!("{event} {param:?}"));
+        }
+    }
+
+    impl EventHandler<Ping> for Listener {
+        fn on_event(
+            &mut self,
+            event: &Ping,
+            _meta: &EventMeta,
+            _object_id: ObjectId,
+            ctx: &mut ContextProxy,
+        ) {
+            self.log.borrow_mut().push(format!("Ping({})", event.0));
+            ctx.emit(Pong);
+        }
+    }
+
+    impl EventHandler<Pong> for Listener {
+        fn on_event(
+            &mut self,
+            _event: &Pong,
+            _meta: &EventMeta,
+            _object_id: ObjectId,
+            _ctx: &mut ContextProxy,
+        ) {
+            self.log.borrow_mut().push("Pong".to_owned());
+        }
+    }
+
+    /// Logs its name on every "click" and optionally consumes it.
+    struct Layer {
+        name: String,
+        priority: i32,
+        consumes: bool,
+        log: Log,
+    }
+
+    impl Controller for Layer {
+        fn on_ready(&mut self, object_id: ObjectId, ctx: &mut ContextProxy) {
+            ctx.listen_event_with_priority("click", object_id, self.priority);
+        }
+
+        fn on_event(
+            &mut self,
+            _event: &str,
+            _param: &dyn Any,
+            _meta: &EventMeta,
+            _object_id: ObjectId,
+            ctx: &mut ContextProxy,
+        ) {
+            self.log.borrow_mut().push(self.name.clone());
+
+            if self.consumes {
+                ctx.consume_event();
+            }
+        }
+    }
+
+    fn spawn_layer(
+        context: &mut Context,
+        log: &Log,
+        name: &str,
+        priority: i32,
+        consumes: bool,
+    ) -> ObjectId {
+        context.with_proxy(|ctx| {
+            let object_id = ctx.create_object();
+            let layer = Layer {
+                name: name.to_owned(),
+                priority,
+                consumes,
+                log: log.clone(),
+            };
+            ctx.attach_controller(object_id, layer);
+            object_id
+        })
+    }
+
+    fn spawn_listener(context: &mut Context) -> (ObjectId, Log) {
+        let log = Log::default();
+        let object_id = context.with_proxy(|ctx| {
+            let object_id = ctx.create_object();
+            ctx.attach_controller(object_id, Listener { log: log.clone() });
+            object_id
+        });
+        (object_id, log)
+    }
+
+    #[test]
+    fn test_typed_events() {
+        let mut context = Context::new();
+        let (object_id, log) = spawn_listener(&mut context);
+
+        context.with_proxy(|ctx| ctx.emit(Ping(7)));
+        assert_eq!(*log.borrow(), vec!["Ping(7)"]);
+
+        // Events emitted by handlers are delivered in the same flush.
+        context.with_proxy(|ctx| ctx.listen::<Pong, Listener>(object_id));
+        context.with_proxy(|ctx| ctx.emit(Ping(8)));
+        assert_eq!(*log.borrow(), vec!["Ping(7)", "Ping(8)", "Pong"]);
+
+        context.with_proxy(|ctx| {
+            ctx.unlisten::<Ping>(object_id);
+            ctx.emit(Ping(9));
+        });
+        assert_eq!(log.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_string_events_receive_param() {
+        let mut context = Context::new();
+        let (_, log) = spawn_listener(&mut context);
+
+        context.with_proxy(|ctx| ctx.emit_event("ping", 3u32));
+        assert_eq!(*log.borrow(), vec!["ping Some(3)"]);
+    }
+
+    #[test]
+    fn test_event_listening_is_a_set() {
+        let mut context = Context::new();
+        let listeners = (0..3)
+            .map(|_| spawn_listener(&mut context))
+            .collect::<Vec<_>>();
+
+        context.with_proxy(|ctx| {
+            for (object_id, _) in &listeners {
+                ctx.listen_event("ping", *object_id);
+                ctx.listen::<Ping, Listener>(*object_id);
+            }
+            ctx.unlisten_event(EventId::new("ping"), listeners[0].0);
+            ctx.emit_event("ping", 1u32);
+            ctx.emit(Ping(2));
+        });
+
+        let logs = listeners
+            .iter()
+            .map(|(_, log)| log.borrow().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(logs[0], vec!["Ping(2)"]);
+        assert_eq!(logs[1], vec!["ping Some(1)", "Ping(2)"]);
+        assert_eq!(logs[2], vec!["ping Some(1)", "Ping(2)"]);
+    }
+
+    #[test]
+    fn test_targeted_events() {
+        let mut context = Context::new();
+        let (a, log_a) = spawn_listener(&mut context);
+        let (b, log_b) = spawn_listener(&mut context);
+
+        context.with_proxy(|ctx| {
+            ctx.emit_event_to(a, "ping", 4u32);
+            ctx.emit_to::<_, Listener>(b, Ping(5));
+            // The target receives the event even though it does not listen to it.
+            ctx.emit_event_to(a, "greet", 6u32);
+        });
+        assert_eq!(*log_a.borrow(), vec!["ping Some(4)", "greet Some(6)"]);
+        assert_eq!(*log_b.borrow(), vec!["Ping(5)"]);
+    }
+
+    #[test]
+    fn test_typed_targeted_events_reach_non_listeners() {
+        struct Quiet {
+            log: Log,
+        }
+
+        impl Controller for Quiet {
+            fn on_event(
+                &mut self,
+                event: &str,
+                _param: &dyn Any,
+                _meta: &EventMeta,
+                _object_id: ObjectId,
+                _ctx: &mut ContextProxy,
+            ) {
+                self.log.borrow_mut().push(event.to_owned());
+            }
+        }
+
+        impl EventHandler<Ping> for Quiet {
+            fn on_event(
+                &mut self,
+                event: &Ping,
+                _meta: &EventMeta,
+                _object_id: ObjectId,
+                _ctx: &mut ContextProxy,
+            ) {
+                self.log.borrow_mut().push(format!("Ping({})", event.0));
+            }
+        }
+
+        let mut context = Context::new();
+        let log = Log::default();
+        let quiet = context.with_proxy(|ctx| {
+            let object_id = ctx.create_object();
+            ctx.attach_controller(object_id, Quiet { log: log.clone() });
+            object_id
+        });
+
+        context.with_proxy(|ctx| {
+            ctx.emit_to::<_, Quiet>(quiet, Ping(1));
+            ctx.emit_to_many::<_, Quiet>([quiet], Ping(2));
+            // Without the handler type a typed event has no way to reach the target.
+            ctx.emit_event_to(quiet, EventId::of::<Ping>(), Ping(3));
+            ctx.emit_event_to(quiet, "greet", ());
+        });
+        assert_eq!(*log.borrow(), vec!["Ping(1)", "Ping(2)", "greet"]);
+    }
+
+    #[test]
+    fn test_targeted_events_to_many() {
+        let mut context = Context::new();
+        let listeners = (0..3)
+            .map(|_| spawn_listener(&mut context))
+            .collect::<Vec<_>>();
+        let (_, bystander_log) = spawn_listener(&mut context);
+
+        context.with_proxy(|ctx| {
+            ctx.remove_object(listeners[1].0);
+            ctx.emit_event_to_many(listeners.iter().map(|(id, _)| *id), "ping", 2u32);
+            ctx.emit_to_many::<_, Listener>([listeners[2].0, listeners[2].0], Ping(3));
+        });
+
+        assert_eq!(*listeners[0].1.borrow(), vec!["ping Some(2)"]);
+        assert!(listeners[1].1.borrow().is_empty());
+        assert_eq!(
+            *listeners[2].1.borrow(),
+            vec!["ping Some(2)", "Ping(3)", "Ping(3)"]
+        );
+        assert!(bystander_log.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_event_meta() {
+        struct Recorder {
+            metas: Rc<RefCell<Vec<EventMeta>>>,
+        }
+
+        impl Controller for Recorder {
+            fn on_ready(&mut self, object_id: ObjectId, ctx: &mut ContextProxy) {
+                ctx.listen_event("ping", object_id);
+                ctx.listen::<Pong, Self>(object_id);
+            }
+
+            fn on_event(
+                &mut self,
+                _event: &str,
+                _param: &dyn Any,
+                meta: &EventMeta,
+                _object_id: ObjectId,
+                _ctx: &mut ContextProxy,
+            ) {
+                self.metas.borrow_mut().push(*meta);
+            }
+        }
+
+        impl EventHandler<Pong> for Recorder {
+            fn on_event(
+                &mut self,
+                _event: &Pong,
+                meta: &EventMeta,
+                _object_id: ObjectId,
+                _ctx: &mut ContextProxy,
+            ) {
+                self.metas.borrow_mut().push(*meta);
+            }
+        }
+
+        let mut context = Context::new();
+        let (listener, _) = spawn_listener(&mut context);
+        let metas = Rc::<RefCell<Vec<EventMeta>>>::default();
+        let recorder = context.with_proxy(|ctx| {
+            let object_id = ctx.create_object();
+            ctx.attach_controller(
+                object_id,
+                Recorder {
+                    metas: metas.clone(),
+                },
+            );
+            object_id
+        });
+
+        // The listener answers `Ping` with a `Pong` of its own.
+        context.with_proxy(|ctx| {
+            ctx.emit_event("ping", 1u32);
+            ctx.emit(Ping(2));
+        });
+        context.proceed_one_frame();
+        context.with_proxy(|ctx| ctx.emit_event_to(recorder, "ping", 3u32));
+
+        assert_eq!(context.frame(), 1);
+        assert_eq!(
+            *metas.borrow(),
+            vec![
+                EventMeta {
+                    sender: EventSender::Host,
+                    frame: 0,
+                    sequence: 0,
+                },
+                EventMeta {
+                    sender: EventSender::Object(listener),
+                    frame: 0,
+                    sequence: 2,
+                },
+                EventMeta {
+                    sender: EventSender::Host,
+                    frame: 1,
+                    sequence: 3,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_event_priorities_and_consumption() {
+        let mut context = Context::new();
+        let log = Log::default();
+        let [world, hud, terrain, dialog] = [
+            ("world", 0, false),
+            ("hud", 5, false),
+            ("terrain", 0, false),
+            ("dialog", 10, true),
+        ]
+        .map(|(name, priority, consumes)| {
+            spawn_layer(&mut context, &log, name, priority, consumes)
+        });
+        let mut click = |f: &dyn Fn(&mut ContextProxy)| {
+            log.borrow_mut().clear();
+            context.with_proxy(f);
+            log.borrow().clone()
+        };
+
+        // The dialog comes first and keeps the click from reaching anything behind it.
+        assert_eq!(click(&|ctx| ctx.emit_event("click", ())), vec!["dialog"]);
+
+        // Equal priorities are delivered in the order the receivers started listening.
+        click(&|ctx| ctx.unlisten_event("click", dialog));
+        assert_eq!(
+            click(&|ctx| ctx.emit_event("click", ())),
+            vec!["hud", "world", "terrain"]
+        );
+
+        // Listening again with another priority moves the receiver.
+        click(&|ctx| ctx.listen_event_with_priority("click", terrain, 20));
+        assert_eq!(
+            click(&|ctx| ctx.emit_event("click", ())),
+            vec!["terrain", "hud", "world"]
+        );
+
+        assert_eq!(
+            click(&|ctx| ctx.emit_event_to_many([hud, dialog, world], "click", ())),
+            vec!["hud", "dialog"]
+        );
+    }
+
+    #[test]
+    fn test_removing_many_prioritized_listeners() {
+        let mut context = Context::new();
+        let log = Log::default();
+        let layers = (0..3000)
+            .map(|i| spawn_layer(&mut context, &log, &i.to_string(), i % 3, false))
+            .collect::<Vec<_>>();
+
+        context.with_proxy(|ctx| {
+            for (i, object_id) in layers.iter().enumerate() {
+                if i % 4 != 0 {
+                    ctx.remove_object(*object_id);
+                }
+            }
+            ctx.emit_event("click", ());
+        });
+
+        let mut expected = (0..3000).step_by(4).collect::<Vec<i32>>();
+        expected.sort_by_key(|i| -(i % 3));
+        let expected = expected.iter().map(i32::to_string).collect::<Vec<_>>();
+        assert_eq!(*log.borrow(), expected);
+
+        // Receivers that listen after the removals still queue up behind their priority.
+        log.borrow_mut().clear();
+        for i in 3000..3003 {
+            spawn_layer(&mut context, &log, &i.to_string(), i % 3, false);
+        }
+        context.with_proxy(|ctx| ctx.emit_event("click", ()));
+
+        let mut expected = (0..3000).step_by(4).chain(3000..3003).collect::<Vec<i32>>();
+        expected.sort_by_key(|i| -(i % 3));
+        let expected = expected.iter().map(i32::to_string).collect::<Vec<_>>();
+        assert_eq!(*log.borrow(), expected);
+    }
+
+    #[test]
+    fn test_replacing_controller_drops_its_listens() {
+        struct Other;
+
+        impl Controller for Other {}
+
+        let mut context = Context::new();
+        let (object_id, log) = spawn_listener(&mut context);
+
+        context.with_proxy(|ctx| {
+            ctx.attach_controller(object_id, Other);
+            ctx.emit(Ping(1));
+        });
+        assert!(log.borrow().is_empty());
+
+        context.with_proxy(|ctx| {
+            ctx.remove_object(object_id);
+            ctx.emit(Ping(2));
+        });
+        assert!(log.borrow().is_empty());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "its controller has another type")]
+    fn test_listen_with_wrong_controller_type() {
+        struct Other;
+
+        impl Controller for Other {}
+
+        impl EventHandler<Ping> for Other {
+            fn on_event(
+                &mut self,
+                _event: &Ping,
+                _meta: &EventMeta,
+                _object_id: ObjectId,
+                _ctx: &mut ContextProxy,
+            ) {
+            }
+        }
 
-        self.next_object_id = result.next_object_id;
-        self.next_component_id = result.next_component_id;
+        let mut context = Context::new();
+        let (object_id, _log) = spawn_listener(&mut context);
+        context.with_proxy(|ctx| ctx.listen::<Ping, Other>(object_id));
     }
 }
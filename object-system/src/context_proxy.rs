@@ -1,8 +1,11 @@
-use crate::{AnyComponent, Component, ComponentId, Controller, Object, ObjectId, ObjectStorage};
+use crate::{
+    event::{dispatch_event, dispatch_named_event, EventDispatcher},
+    AnyComponent, Component, ComponentId, Controller, Event, EventHandler, EventId, EventSender,
+    Object, ObjectId, ObjectStorage,
+};
 use std::{
     any::{Any, TypeId},
     collections::HashSet,
-    num::NonZeroU32,
 };
 
 pub(crate) enum ContextActionItem {
@@ -29,53 +32,77 @@ pub(crate) enum ContextActionItem {
         object_id: ObjectId,
     },
     ListenEvent {
-        event: String,
+        event: EventId,
         object_id: ObjectId,
+        priority: i32,
+        dispatcher: EventDispatcher,
+        /// The controller type a typed dispatcher downcasts to, checked when the listen is
+        /// processed.
+        controller_type: Option<TypeId>,
     },
     UnlistenEvent {
-        event: String,
+        event: EventId,
         object_id: ObjectId,
     },
     UnlistenEventAll {
         object_id: ObjectId,
     },
     EmitEvent {
-        event: String,
+        sender: EventSender,
+        event: EventId,
+        param: Box<dyn Any>,
+    },
+    EmitEventTo {
+        sender: EventSender,
+        event: EventId,
+        target: ObjectId,
+        param: Box<dyn Any>,
+        /// Delivers a typed event to a target that does not listen to it.
+        dispatcher: Option<EventDispatcher>,
+    },
+    EmitEventToMany {
+        sender: EventSender,
+        event: EventId,
+        targets: Vec<ObjectId>,
         param: Box<dyn Any>,
+        dispatcher: Option<EventDispatcher>,
     },
 }
 
 pub(crate) struct ContextResult {
-    pub next_object_id: NonZeroU32,
-    pub next_component_id: NonZeroU32,
     pub action_queue: Vec<ContextActionItem>,
 }
 
 pub struct ContextProxy<'ctx> {
-    next_object_id: NonZeroU32,
-    next_component_id: NonZeroU32,
     object_storage: &'ctx mut ObjectStorage,
     action_queue: Vec<ContextActionItem>,
+    sender: EventSender,
+    event_consumed: bool,
 }
 
 impl<'ctx> ContextProxy<'ctx> {
-    pub(crate) fn new(
-        next_object_id: NonZeroU32,
-        next_component_id: NonZeroU32,
-        object_storage: &'ctx mut ObjectStorage,
-    ) -> Self {
+    pub(crate) fn new(object_storage: &'ctx mut ObjectStorage) -> Self {
         Self {
-            next_object_id,
-            next_component_id,
             object_storage,
             action_queue: Vec::new(),
+            sender: EventSender::Host,
+            event_consumed: false,
         }
     }
 
+    /// Attributes the events emitted from now on to the controller of `object_id`. Called before
+    /// handing the proxy to a controller.
+    pub(crate) fn act_as(&mut self, object_id: ObjectId) {
+        self.sender = EventSender::Object(object_id);
+        self.event_consumed = false;
+    }
+
+    pub(crate) fn take_event_consumed(&mut self) -> bool {
+        std::mem::take(&mut self.event_consumed)
+    }
+
     pub(crate) fn into_result(self) -> ContextResult {
         ContextResult {
-            next_object_id: self.next_object_id,
-            next_component_id: self.next_component_id,
             action_queue: self.action_queue,
         }
     }
@@ -96,23 +123,11 @@ impl<'ctx> ContextProxy<'ctx> {
     }
 
     pub fn create_object(&mut self) -> ObjectId {
-        let object_id = ObjectId::new(self.next_object_id);
-        self.next_object_id = self.next_object_id.saturating_add(1);
-
-        let object = Object::new(object_id);
-        self.object_storage.add(object);
-
-        object_id
+        self.object_storage.add(vec![])
     }
 
     pub fn create_object_with_components(&mut self, components: Vec<AnyComponent>) -> ObjectId {
-        let object_id = ObjectId::new(self.next_object_id);
-        self.next_object_id = self.next_object_id.saturating_add(1);
-
-        let object = Object::with_components(object_id, components);
-        self.object_storage.add(object);
-
-        object_id
+        self.object_storage.add(components)
     }
 
     pub fn remove_object(&mut self, object_id: ObjectId) {
@@ -120,36 +135,20 @@ impl<'ctx> ContextProxy<'ctx> {
             .push(ContextActionItem::RemoveObject { object_id });
     }
 
+    pub fn find_object_id_by_component_id(&self, component_id: ComponentId) -> Option<ObjectId> {
+        self.object_storage.component_owner(component_id)
+    }
+
     pub fn add_component<T>(&mut self, object_id: ObjectId, component: T) -> Option<ComponentId>
     where
         T: Component,
     {
-        match self.object_storage.get_mut(object_id) {
-            Some(object) => {
-                let component_id = ComponentId::new(self.next_component_id);
-                self.next_component_id = self.next_component_id.saturating_add(1);
-
-                let component = AnyComponent::new(component_id, component);
-                object.add_component(component);
-
-                self.object_storage
-                    .register_component(object_id, TypeId::of::<T>());
-
-                Some(component_id)
-            }
-            None => None,
-        }
+        self.object_storage.add_component(object_id, component)
     }
 
     pub fn remove_component(&mut self, object_id: ObjectId, component_id: ComponentId) {
-        if let Some(object) = self.object_storage.get_mut(object_id) {
-            if let Some(component) = object.remove_component(component_id) {
-                self.object_storage
-                    .unregister_component(object_id, component.type_id());
-
-                // TODO: de-allocate the component id
-            }
-        }
+        self.object_storage
+            .remove_component(object_id, component_id);
     }
 
     pub fn attach_controller<T>(&mut self, object_id: ObjectId, controller: T)
@@ -187,14 +186,108 @@ impl<'ctx> ContextProxy<'ctx> {
             .push(ContextActionItem::UnlistenOnLateUpdate { object_id });
     }
 
-    pub fn listen_event(&mut self, event: impl Into<String>, object_id: ObjectId) {
+    /// Delivers events of type `E` to the controller of `object_id`, which has to be a `C`. The
+    /// listen is rejected, with a debug assertion, if the controller attached by the time it is
+    /// processed is of another type.
+    /// Controllers usually call this from [`Controller::on_ready`] as
+    /// `ctx.listen::<E, Self>(object_id)`.
+    pub fn listen<E, C>(&mut self, object_id: ObjectId)
+    where
+        E: Event,
+        C: EventHandler<E>,
+    {
+        self.listen_with_priority::<E, C>(object_id, 0);
+    }
+
+    /// Like [`listen`](Self::listen), but receivers with a higher `priority` get the event first.
+    /// The default priority is zero.
+    pub fn listen_with_priority<E, C>(&mut self, object_id: ObjectId, priority: i32)
+    where
+        E: Event,
+        C: EventHandler<E>,
+    {
+        self.action_queue.push(ContextActionItem::ListenEvent {
+            event: EventId::of::<E>(),
+            object_id,
+            priority,
+            dispatcher: dispatch_event::<E, C>,
+            controller_type: Some(TypeId::of::<C>()),
+        });
+    }
+
+    pub fn unlisten<E>(&mut self, object_id: ObjectId)
+    where
+        E: Event,
+    {
+        self.unlisten_event(EventId::of::<E>(), object_id);
+    }
+
+    pub fn emit<E>(&mut self, event: E)
+    where
+        E: Event,
+    {
+        self.emit_event(EventId::of::<E>(), event);
+    }
+
+    /// Delivers a typed event to the controller of `target` only, see
+    /// [`emit_event_to`](Self::emit_event_to). A target that does not listen to `E` receives it
+    /// through [`EventHandler`] all the same, so its controller has to be a `C`:
+    /// `ctx.emit_to::<E, TargetController>(target, event)`.
+    pub fn emit_to<E, C>(&mut self, target: ObjectId, event: E)
+    where
+        E: Event,
+        C: EventHandler<E>,
+    {
+        self.action_queue.push(ContextActionItem::EmitEventTo {
+            sender: self.sender,
+            event: EventId::of::<E>(),
+            target,
+            param: Box::new(event),
+            dispatcher: Some(dispatch_event::<E, C>),
+        });
+    }
+
+    /// Delivers a typed event to the controller of every object in `targets`, see
+    /// [`emit_event_to_many`](Self::emit_event_to_many) and [`emit_to`](Self::emit_to).
+    pub fn emit_to_many<E, C>(&mut self, targets: impl IntoIterator<Item = ObjectId>, event: E)
+    where
+        E: Event,
+        C: EventHandler<E>,
+    {
+        self.action_queue.push(ContextActionItem::EmitEventToMany {
+            sender: self.sender,
+            event: EventId::of::<E>(),
+            targets: targets.into_iter().collect(),
+            param: Box::new(event),
+            dispatcher: Some(dispatch_event::<E, C>),
+        });
+    }
+
+    /// Delivers the named event to [`Controller::on_event`] of `object_id`. Listening again to
+    /// the same event only updates the priority. Passing a pre-interned [`EventId`] instead of a
+    /// name skips the name lookup.
+    pub fn listen_event(&mut self, event: impl Into<EventId>, object_id: ObjectId) {
+        self.listen_event_with_priority(event, object_id, 0);
+    }
+
+    /// Like [`listen_event`](Self::listen_event), but receivers with a higher `priority` get the
+    /// event first.
+    pub fn listen_event_with_priority(
+        &mut self,
+        event: impl Into<EventId>,
+        object_id: ObjectId,
+        priority: i32,
+    ) {
         self.action_queue.push(ContextActionItem::ListenEvent {
             event: event.into(),
             object_id,
+            priority,
+            dispatcher: dispatch_named_event,
+            controller_type: None,
         });
     }
 
-    pub fn unlisten_event(&mut self, event: impl Into<String>, object_id: ObjectId) {
+    pub fn unlisten_event(&mut self, event: impl Into<EventId>, object_id: ObjectId) {
         self.action_queue.push(ContextActionItem::UnlistenEvent {
             event: event.into(),
             object_id,
@@ -206,10 +299,50 @@ impl<'ctx> ContextProxy<'ctx> {
             .push(ContextActionItem::UnlistenEventAll { object_id });
     }
 
-    pub fn emit_event(&mut self, event: impl Into<String>, param: impl Any) {
+    /// Marks the event being handled as consumed, so that no further receiver gets it. Only has an
+    /// effect when called from an event handler.
+    pub fn consume_event(&mut self) {
+        self.event_consumed = true;
+    }
+
+    pub fn emit_event(&mut self, event: impl Into<EventId>, param: impl Any) {
         self.action_queue.push(ContextActionItem::EmitEvent {
+            sender: self.sender,
+            event: event.into(),
+            param: Box::new(param),
+        });
+    }
+
+    /// Delivers an event to the controller of `target` only, whether or not it listens to the
+    /// event. A target that listens receives the event the way it registered for it, through
+    /// [`EventHandler`] for typed events. A target that does not listen receives a named event
+    /// through [`Controller::on_event`], but a typed one only through [`emit_to`](Self::emit_to),
+    /// which knows the handler type.
+    pub fn emit_event_to(&mut self, target: ObjectId, event: impl Into<EventId>, param: impl Any) {
+        self.action_queue.push(ContextActionItem::EmitEventTo {
+            sender: self.sender,
+            event: event.into(),
+            target,
+            param: Box::new(param),
+            dispatcher: None,
+        });
+    }
+
+    /// Delivers an event to the controller of every object in `targets`, in order, like
+    /// [`emit_event_to`](Self::emit_event_to), until one of them consumes it. An object listed
+    /// twice receives the event twice.
+    pub fn emit_event_to_many(
+        &mut self,
+        targets: impl IntoIterator<Item = ObjectId>,
+        event: impl Into<EventId>,
+        param: impl Any,
+    ) {
+        self.action_queue.push(ContextActionItem::EmitEventToMany {
+            sender: self.sender,
             event: event.into(),
+            targets: targets.into_iter().collect(),
             param: Box::new(param),
+            dispatcher: None,
         });
     }
 }
@@ -1,4 +1,4 @@
-use crate::{object_id::ObjectId, ContextProxy};
+use crate::{object_id::ObjectId, ContextProxy, EventMeta};
 use std::any::Any;
 
 pub trait Controller: Any {
@@ -10,6 +10,7 @@ pub trait Controller: Any {
         &mut self,
         _event: &str,
         _param: &dyn Any,
+        _meta: &EventMeta,
         _object_id: ObjectId,
         _ctx: &mut ContextProxy,
     ) {
@@ -0,0 +1,85 @@
+use crate::{ContextProxy, Controller, ObjectId};
+use std::any::Any;
+
+/// Who emitted an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventSender {
+    /// Emitted from [`Context::with_proxy`](crate::Context::with_proxy), outside of any controller.
+    Host,
+    /// Emitted by the controller of this object.
+    Object(ObjectId),
+}
+
+/// Describes a delivered event beyond its payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventMeta {
+    pub sender: EventSender,
+    /// The frame the event was emitted in, see [`Context::frame`](crate::Context::frame).
+    pub frame: u64,
+    /// Counts the emissions of the context, starting at zero. Every target of one emission sees
+    /// the same sequence number.
+    pub sequence: u64,
+}
+
+/// A typed event. The event type itself identifies the event, and its value is the payload, so
+/// there is no name to misspell and no parameter to downcast.
+pub trait Event: Any {}
+
+/// Implemented by controllers that handle events of type `E`, see [`ContextProxy::listen`].
+pub trait EventHandler<E>: Controller
+where
+    E: Event,
+{
+    fn on_event(
+        &mut self,
+        event: &E,
+        meta: &EventMeta,
+        object_id: ObjectId,
+        ctx: &mut ContextProxy,
+    );
+}
+
+/// Delivers a type-erased event to a type-erased controller.
+pub(crate) type EventDispatcher =
+    fn(&mut dyn Controller, &'static str, &dyn Any, &EventMeta, ObjectId, &mut ContextProxy);
+
+/// Delivers a named event to [`Controller::on_event`].
+pub(crate) fn dispatch_named_event(
+    controller: &mut dyn Controller,
+    event_name: &'static str,
+    param: &dyn Any,
+    meta: &EventMeta,
+    object_id: ObjectId,
+    ctx: &mut ContextProxy,
+) {
+    controller.on_event(event_name, param, meta, object_id, ctx);
+}
+
+/// Recovers the concrete types erased by [`EventDispatcher`]. Listens naming the wrong controller
+/// type are rejected and replacing a controller drops its listens, so the downcast only fails on a
+/// bug, which is skipped in release builds. A payload of another type than `E` is skipped.
+pub(crate) fn dispatch_event<E, C>(
+    controller: &mut dyn Controller,
+    _event_name: &'static str,
+    value: &dyn Any,
+    meta: &EventMeta,
+    object_id: ObjectId,
+    ctx: &mut ContextProxy,
+) where
+    E: Event,
+    C: EventHandler<E>,
+{
+    let controller: &mut dyn Any = controller;
+    let Some(controller) = controller.downcast_mut::<C>() else {
+        debug_assert!(
+            false,
+            "{object_id:?} does not have a {} controller",
+            std::any::type_name::<C>()
+        );
+        return;
+    };
+
+    if let Some(event) = value.downcast_ref::<E>() {
+        EventHandler::on_event(controller, event, meta, object_id, ctx);
+    }
+}
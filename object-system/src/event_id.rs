@@ -0,0 +1,143 @@
+use crate::Event;
+use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+    fmt::{self, Debug, Display},
+    sync::{LazyLock, PoisonError, RwLock},
+};
+
+/// An interned event identifier.
+///
+/// Names are interned once per process; afterwards an `EventId` is a plain integer, so
+/// listening, unlistening and emitting neither allocate nor hash the name again. Interning the
+/// same name, or the same [`Event`] type, always yields the same id.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EventId(u32);
+
+#[derive(Default)]
+struct Registry {
+    by_name: HashMap<&'static str, EventId>,
+    by_type: HashMap<TypeId, EventId>,
+    names: Vec<&'static str>,
+    typed: Vec<bool>,
+}
+
+impl Registry {
+    fn push(&mut self, name: &'static str, typed: bool) -> EventId {
+        let id = EventId(self.names.len() as u32);
+        self.names.push(name);
+        self.typed.push(typed);
+        id
+    }
+}
+
+static REGISTRY: LazyLock<RwLock<Registry>> = LazyLock::new(Default::default);
+
+impl EventId {
+    /// Interns the event called `name`.
+    pub fn new(name: &str) -> Self {
+        let registry = REGISTRY.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some(&id) = registry.by_name.get(name) {
+            return id;
+        }
+        drop(registry);
+
+        let mut registry = REGISTRY.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(&id) = registry.by_name.get(name) {
+            return id;
+        }
+
+        // Every distinct name is leaked exactly once, so the registry can hand out `&'static str`.
+        let name: &'static str = Box::leak(name.into());
+        let id = registry.push(name, false);
+        registry.by_name.insert(name, id);
+        id
+    }
+
+    /// Interns the typed event `E`, named after its type.
+    pub fn of<E>() -> Self
+    where
+        E: Event,
+    {
+        let type_id = TypeId::of::<E>();
+
+        let registry = REGISTRY.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some(&id) = registry.by_type.get(&type_id) {
+            return id;
+        }
+        drop(registry);
+
+        let mut registry = REGISTRY.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(&id) = registry.by_type.get(&type_id) {
+            return id;
+        }
+
+        let id = registry.push(type_name::<E>(), true);
+        registry.by_type.insert(type_id, id);
+        id
+    }
+
+    pub fn name(self) -> &'static str {
+        let registry = REGISTRY.read().unwrap_or_else(PoisonError::into_inner);
+        registry.names[self.0 as usize]
+    }
+
+    /// Returns whether the id was interned by [`EventId::of`] rather than by name.
+    pub fn is_typed(self) -> bool {
+        let registry = REGISTRY.read().unwrap_or_else(PoisonError::into_inner);
+        registry.typed[self.0 as usize]
+    }
+
+    pub(crate) fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl From<&str> for EventId {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for EventId {
+    fn from(name: String) -> Self {
+        Self::new(&name)
+    }
+}
+
+impl Debug for EventId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("EventId").field(&self.name()).finish()
+    }
+}
+
+impl Display for EventId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Interned;
+
+    impl Event for Interned {}
+
+    #[test]
+    fn test_event_id_interning() {
+        let a = EventId::new("event-id-test-a");
+        assert_eq!(EventId::from("event-id-test-a".to_owned()), a);
+        assert_ne!(EventId::new("event-id-test-b"), a);
+        assert_eq!(a.name(), "event-id-test-a");
+        assert_eq!(a.to_string(), "event-id-test-a");
+
+        let typed = EventId::of::<Interned>();
+        assert_eq!(EventId::of::<Interned>(), typed);
+        assert_ne!(EventId::new(typed.name()), typed);
+        assert!(typed.name().ends_with("Interned"));
+        assert!(typed.is_typed());
+        assert!(!a.is_typed());
+    }
+}
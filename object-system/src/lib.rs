@@ -4,6 +4,8 @@ mod component_id;
 mod context;
 mod context_proxy;
 mod controller;
+mod event;
+mod event_id;
 mod object;
 mod object_id;
 mod storage;
@@ -14,6 +16,8 @@ pub use component_id::*;
 pub use context::*;
 pub use context_proxy::*;
 pub use controller::*;
+pub use event::*;
+pub use event_id::*;
 pub use object::*;
 pub use object_id::*;
 pub use storage::*;
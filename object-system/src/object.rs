@@ -9,13 +9,6 @@ pub struct Object {
 }
 
 impl Object {
-    pub(crate) fn new(id: ObjectId) -> Self {
-        Self {
-            id,
-            components: vec![],
-        }
-    }
-
     pub(crate) fn with_components(id: ObjectId, components: Vec<AnyComponent>) -> Self {
         Self { id, components }
     }
@@ -1,10 +1,16 @@
-use std::num::NonZeroU32;
+use slotmap::Key;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct ObjectId(NonZeroU32);
+slotmap::new_key_type! {
+    pub struct ObjectId;
+}
 
 impl ObjectId {
-    pub(crate) fn new(id: NonZeroU32) -> Self {
-        Self(id)
+    /// Packs the id into a non-zero `u64` that [`ObjectId::from_bits`] turns back into the same id.
+    pub fn to_bits(&self) -> u64 {
+        Key::to_bits(self)
+    }
+
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        Key::from_bits(bits)
     }
 }
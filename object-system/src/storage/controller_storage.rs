@@ -1,5 +1,10 @@
-use crate::{controller::Controller, object_id::ObjectId, ContextProxy};
-use std::collections::{hash_map::Entry, HashMap, HashSet};
+use crate::{
+    controller::Controller, event::EventDispatcher, object_id::ObjectId, ContextProxy, EventMeta,
+};
+use std::{
+    any::{Any, TypeId},
+    collections::{hash_map::Entry, HashMap, HashSet},
+};
 
 pub struct ControllerStorage {
     controllers: HashMap<ObjectId, Box<dyn Controller>>,
@@ -16,8 +21,10 @@ impl ControllerStorage {
         }
     }
 
-    pub(crate) fn find_controller(&mut self, id: ObjectId) -> Option<&mut dyn Controller> {
-        self.controllers.get_mut(&id).map(|c| c.as_mut())
+    /// Returns the concrete type of the controller attached to `id`.
+    pub(crate) fn controller_type(&self, id: ObjectId) -> Option<TypeId> {
+        let controller: &dyn Any = self.controllers.get(&id)?.as_ref();
+        Some(controller.type_id())
     }
 
     pub(crate) fn attach_controller(
@@ -26,6 +33,8 @@ impl ControllerStorage {
         controller: Box<dyn Controller>,
         ctx: &mut ContextProxy,
     ) {
+        ctx.act_as(id);
+
         match self.controllers.entry(id) {
             Entry::Occupied(mut entry) => {
                 entry.get_mut().on_destroy(id, ctx);
@@ -43,6 +52,7 @@ impl ControllerStorage {
         self.on_late_update_hooked_controllers.remove(&id);
 
         if let Some(mut controller) = self.controllers.remove(&id) {
+            ctx.act_as(id);
             controller.on_destroy(id, ctx);
         }
     }
@@ -67,9 +77,30 @@ impl ControllerStorage {
         self.on_late_update_hooked_controllers.remove(&id);
     }
 
+    /// Delivers an event to the controller of `id` through `dispatcher`, returning whether the
+    /// controller consumed it.
+    pub(crate) fn dispatch_event(
+        &mut self,
+        id: ObjectId,
+        event_name: &'static str,
+        dispatcher: EventDispatcher,
+        param: &dyn Any,
+        meta: &EventMeta,
+        ctx: &mut ContextProxy,
+    ) -> bool {
+        let Some(controller) = self.controllers.get_mut(&id) else {
+            return false;
+        };
+
+        ctx.act_as(id);
+        dispatcher(controller.as_mut(), event_name, param, meta, id, ctx);
+        ctx.take_event_consumed()
+    }
+
     pub(crate) fn invoke_on_update(&mut self, ctx: &mut ContextProxy) {
         for id in &self.on_update_hooked_controllers {
             if let Some(controller) = self.controllers.get_mut(id) {
+                ctx.act_as(*id);
                 controller.on_update(*id, ctx);
             }
         }
@@ -78,6 +109,7 @@ impl ControllerStorage {
     pub(crate) fn invoke_on_late_update(&mut self, ctx: &mut ContextProxy) {
         for id in &self.on_late_update_hooked_controllers {
             if let Some(controller) = self.controllers.get_mut(id) {
+                ctx.act_as(*id);
                 controller.on_late_update(*id, ctx);
             }
         }
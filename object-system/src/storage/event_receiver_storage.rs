@@ -1,68 +1,253 @@
-use crate::{ContextProxy, ControllerStorage, ObjectId};
+use crate::{
+    event::{dispatch_named_event, EventDispatcher},
+    ContextProxy, ControllerStorage, EventId, EventMeta, ObjectId,
+};
 use std::{
     any::Any,
-    collections::{BTreeSet, HashMap},
+    collections::{HashMap, HashSet},
 };
 
 pub struct EventReceiverStorage {
-    event_to_object_ids: HashMap<String, BTreeSet<ObjectId>>,
-    object_id_to_events: HashMap<ObjectId, Vec<String>>,
+    /// Indexed by [`EventId`], whose values are small and dense.
+    event_receivers: Vec<EventReceivers>,
+    object_id_to_events: HashMap<ObjectId, HashSet<EventId>>,
+}
+
+/// The receivers of a single event in one bucket per priority, with the position of every
+/// receiver so that it can be found and removed in constant time.
+#[derive(Default)]
+struct EventReceivers {
+    /// Cached so that dispatching does not have to consult the global registry.
+    event_name: &'static str,
+    /// Never shrinks, so that positions stay valid.
+    buckets: Vec<PriorityBucket>,
+    /// Indices into `buckets` by descending priority, the order of delivery.
+    bucket_order: Vec<usize>,
+    bucket_indices: HashMap<i32, usize>,
+    positions: HashMap<ObjectId, ReceiverPosition>,
+}
+
+/// The receivers of one priority in the order they started listening. Removed receivers leave a
+/// tombstone, which is compacted away once tombstones outnumber the receivers.
+struct PriorityBucket {
+    priority: i32,
+    slots: Vec<Option<Receiver>>,
+    len: usize,
+}
+
+#[derive(Clone, Copy)]
+struct ReceiverPosition {
+    bucket: usize,
+    slot: usize,
+}
+
+struct Receiver {
+    object_id: ObjectId,
+    dispatcher: EventDispatcher,
 }
 
 impl EventReceiverStorage {
     pub fn new() -> Self {
         Self {
-            event_to_object_ids: HashMap::new(),
+            event_receivers: Vec::new(),
             object_id_to_events: HashMap::new(),
         }
     }
 
-    pub fn listen(&mut self, event: String, object_id: ObjectId) {
-        self.event_to_object_ids
-            .entry(event.clone())
-            .or_default()
-            .insert(object_id);
-        self.object_id_to_events
-            .entry(object_id)
-            .or_default()
-            .push(event);
-    }
+    /// Registers `object_id` for `event`. Registering again replaces the dispatcher, and moves
+    /// the receiver only if its priority changed.
+    pub(crate) fn listen(
+        &mut self,
+        event: EventId,
+        object_id: ObjectId,
+        priority: i32,
+        dispatcher: EventDispatcher,
+    ) {
+        if self.event_receivers.len() <= event.index() {
+            self.event_receivers
+                .resize_with(event.index() + 1, Default::default);
+        }
 
-    pub fn unlisten(&mut self, event: String, object_id: ObjectId) {
-        if let Some(object_ids) = self.event_to_object_ids.get_mut(&event) {
-            object_ids.remove(&object_id);
+        let event_receivers = &mut self.event_receivers[event.index()];
+        event_receivers.event_name = event.name();
+
+        let receiver = Receiver {
+            object_id,
+            dispatcher,
+        };
+
+        match event_receivers.positions.get(&object_id).copied() {
+            Some(position) if event_receivers.buckets[position.bucket].priority == priority => {
+                event_receivers.buckets[position.bucket].slots[position.slot] = Some(receiver);
+            }
+            Some(_) => {
+                event_receivers.remove(object_id);
+                event_receivers.insert(priority, receiver);
+            }
+            None => {
+                event_receivers.insert(priority, receiver);
+                self.object_id_to_events
+                    .entry(object_id)
+                    .or_default()
+                    .insert(event);
+            }
         }
+    }
+
+    pub fn unlisten(&mut self, event: EventId, object_id: ObjectId) {
+        self.remove_receiver(event, object_id);
 
         if let Some(events) = self.object_id_to_events.get_mut(&object_id) {
-            if let Some(index) = events.iter().position(|e| e.as_str() == event) {
-                events.swap_remove(index);
-            }
+            events.remove(&event);
         }
     }
 
     pub fn unlisten_all(&mut self, object_id: ObjectId) {
         if let Some(events) = self.object_id_to_events.remove(&object_id) {
             for event in events {
-                if let Some(object_ids) = self.event_to_object_ids.get_mut(&event) {
-                    object_ids.remove(&object_id);
-                }
+                self.remove_receiver(event, object_id);
             }
         }
     }
 
+    fn remove_receiver(&mut self, event: EventId, object_id: ObjectId) {
+        if let Some(event_receivers) = self.event_receivers.get_mut(event.index()) {
+            event_receivers.remove(object_id);
+        }
+    }
+
+    /// Delivers the event to its receivers by descending priority, receivers of equal priority in
+    /// the order they started listening, until one of them consumes it.
     pub fn emit(
         &self,
-        event: &str,
+        event: EventId,
         param: &dyn Any,
+        meta: &EventMeta,
         ctx: &mut ContextProxy,
         controller_storage: &mut ControllerStorage,
     ) {
-        if let Some(object_ids) = self.event_to_object_ids.get(event) {
-            for object_id in object_ids {
-                if let Some(controller) = controller_storage.find_controller(*object_id) {
-                    controller.on_event(event, param, *object_id, ctx);
+        if let Some(event_receivers) = self.event_receivers.get(event.index()) {
+            for receiver in event_receivers.receivers() {
+                let consumed = controller_storage.dispatch_event(
+                    receiver.object_id,
+                    event_receivers.event_name,
+                    receiver.dispatcher,
+                    param,
+                    meta,
+                    ctx,
+                );
+
+                if consumed {
+                    break;
                 }
             }
         }
     }
+
+    /// Picks the dispatcher that delivers the event to `target` alone: its registered one if it
+    /// listens. Otherwise a typed event goes through `fallback`, and is dropped without one, while
+    /// a named event goes to [`Controller::on_event`](crate::Controller::on_event).
+    pub fn directed_dispatcher(
+        &self,
+        event: EventId,
+        target: ObjectId,
+        fallback: Option<EventDispatcher>,
+    ) -> Option<(&'static str, EventDispatcher)> {
+        let registered = self
+            .event_receivers
+            .get(event.index())
+            .and_then(|event_receivers| {
+                let position = event_receivers.positions.get(&target)?;
+                let receiver = event_receivers.buckets[position.bucket].slots[position.slot]
+                    .as_ref()
+                    .expect("a listed receiver is never a tombstone");
+                Some((event_receivers.event_name, receiver.dispatcher))
+            });
+
+        registered.or_else(|| {
+            let dispatcher = if event.is_typed() {
+                fallback?
+            } else {
+                dispatch_named_event
+            };
+            Some((event.name(), dispatcher))
+        })
+    }
+}
+
+impl EventReceivers {
+    /// Iterates over the receivers in the order of delivery.
+    fn receivers(&self) -> impl Iterator<Item = &Receiver> {
+        self.bucket_order
+            .iter()
+            .flat_map(|&bucket| self.buckets[bucket].slots.iter().flatten())
+    }
+
+    /// Inserts the receiver behind every receiver of the same or a higher priority.
+    fn insert(&mut self, priority: i32, receiver: Receiver) {
+        let bucket = match self.bucket_indices.get(&priority) {
+            Some(&bucket) => bucket,
+            None => self.insert_bucket(priority),
+        };
+
+        let slots = &mut self.buckets[bucket].slots;
+        let position = ReceiverPosition {
+            bucket,
+            slot: slots.len(),
+        };
+        self.positions.insert(receiver.object_id, position);
+        slots.push(Some(receiver));
+        self.buckets[bucket].len += 1;
+    }
+
+    /// Adds an empty bucket for `priority`. Distinct priorities are few, so the linear search
+    /// for its place in the delivery order is cheap.
+    fn insert_bucket(&mut self, priority: i32) -> usize {
+        let bucket = self.buckets.len();
+        self.buckets.push(PriorityBucket {
+            priority,
+            slots: Vec::new(),
+            len: 0,
+        });
+        self.bucket_indices.insert(priority, bucket);
+
+        let index = self
+            .bucket_order
+            .iter()
+            .position(|&other| self.buckets[other].priority < priority)
+            .unwrap_or(self.bucket_order.len());
+        self.bucket_order.insert(index, bucket);
+        bucket
+    }
+
+    fn remove(&mut self, object_id: ObjectId) {
+        let Some(position) = self.positions.remove(&object_id) else {
+            return;
+        };
+
+        let bucket = &mut self.buckets[position.bucket];
+        bucket.slots[position.slot] = None;
+        bucket.len -= 1;
+
+        // Compacting only once tombstones outnumber the receivers keeps removal amortized O(1).
+        if bucket.slots.len() > 2 * bucket.len {
+            bucket.slots.retain(Option::is_some);
+
+            for (slot, receiver) in bucket.slots.iter().flatten().enumerate() {
+                self.positions.insert(
+                    receiver.object_id,
+                    ReceiverPosition {
+                        bucket: position.bucket,
+                        slot,
+                    },
+                );
+            }
+        }
+    }
+}
+
+impl Default for EventReceiverStorage {
+    fn default() -> Self {
+        Self::new()
+    }
 }
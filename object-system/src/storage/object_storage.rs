@@ -1,28 +1,34 @@
-use crate::{object::Object, object_id::ObjectId, Component};
+use crate::{
+    any_component::AnyComponent, component_id::ComponentId, object::Object, object_id::ObjectId,
+    Component,
+};
+use slotmap::SlotMap;
 use std::{
     any::TypeId,
     collections::{HashMap, HashSet},
 };
 
 pub struct ObjectStorage {
-    objects: HashMap<ObjectId, Object>,
+    objects: SlotMap<ObjectId, Object>,
+    component_owners: SlotMap<ComponentId, ObjectId>,
     component_type_indices: HashMap<TypeId, HashSet<ObjectId>>,
 }
 
 impl ObjectStorage {
     pub fn new() -> Self {
         Self {
-            objects: HashMap::new(),
+            objects: SlotMap::with_key(),
+            component_owners: SlotMap::with_key(),
             component_type_indices: HashMap::new(),
         }
     }
 
     pub fn get(&self, id: ObjectId) -> Option<&Object> {
-        self.objects.get(&id)
+        self.objects.get(id)
     }
 
     pub fn get_mut(&mut self, id: ObjectId) -> Option<&mut Object> {
-        self.objects.get_mut(&id)
+        self.objects.get_mut(id)
     }
 
     pub fn object_ids_with_component<T>(&self) -> Option<&HashSet<ObjectId>>
@@ -32,28 +38,74 @@ impl ObjectStorage {
         self.component_type_indices.get(&TypeId::of::<T>())
     }
 
-    pub(crate) fn add(&mut self, object: Object) {
-        for component in object.components() {
-            self.register_component(object.id(), component.type_id());
+    /// Returns the object owning the given component, or `None` if the component has been removed.
+    pub fn component_owner(&self, component_id: ComponentId) -> Option<ObjectId> {
+        self.component_owners.get(component_id).copied()
+    }
+
+    pub(crate) fn add(&mut self, mut components: Vec<AnyComponent>) -> ObjectId {
+        let type_ids = components
+            .iter()
+            .map(|component| component.type_id())
+            .collect::<Vec<_>>();
+        let entry = self.objects.vacant_entry();
+        let id = entry.id();
+
+        for component in &mut components {
+            component.set_id(self.component_owners.add(id));
+        }
+
+        entry.insert(Object::with_components(id, components));
+
+        for type_id in type_ids {
+            self.register_component(id, type_id);
         }
 
-        self.objects.entry(object.id()).or_insert(object);
+        id
     }
 
     pub(crate) fn remove(&mut self, id: ObjectId) {
-        if let Some(object) = self.objects.remove(&id) {
+        if let Some(object) = self.objects.remove(id) {
             for component in object.components() {
+                self.component_owners.remove(component.id());
                 self.unregister_component(id, component.type_id());
             }
         }
+    }
+
+    pub(crate) fn add_component<T>(
+        &mut self,
+        object_id: ObjectId,
+        component: T,
+    ) -> Option<ComponentId>
+    where
+        T: Component,
+    {
+        let object = self.objects.get_mut(object_id)?;
+        let component_id = self.component_owners.add(object_id);
+        object.add_component(AnyComponent::new(component_id, component));
+        self.register_component(object_id, TypeId::of::<T>());
+
+        Some(component_id)
+    }
+
+    pub(crate) fn remove_component(
+        &mut self,
+        object_id: ObjectId,
+        component_id: ComponentId,
+    ) -> Option<AnyComponent> {
+        let object = self.objects.get_mut(object_id)?;
+        let component = object.remove_component(component_id)?;
+        self.component_owners.remove(component_id);
+        self.unregister_component(object_id, component.type_id());
 
-        // TODO: de-allocate the object id
+        Some(component)
     }
 
     pub(crate) fn register_component(&mut self, object_id: ObjectId, type_id: TypeId) {
         self.component_type_indices
             .entry(type_id)
-            .or_insert(HashSet::new())
+            .or_default()
             .insert(object_id);
     }
 
@@ -63,3 +115,81 @@ impl ObjectStorage {
         }
     }
 }
+
+impl Default for ObjectStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_storage_add() {
+        let mut storage = ObjectStorage::new();
+        let id = storage.add(vec![]);
+        assert_eq!(storage.get(id).map(|object| object.id()), Some(id));
+    }
+
+    #[test]
+    fn test_object_storage_stale_id() {
+        let mut storage = ObjectStorage::new();
+        let id1 = storage.add(vec![]);
+        storage.remove(id1);
+        assert!(storage.get(id1).is_none());
+
+        let id2 = storage.add(vec![]);
+        assert_ne!(id1, id2);
+        assert!(storage.get(id1).is_none());
+        assert_eq!(storage.get(id2).map(|object| object.id()), Some(id2));
+    }
+
+    struct TestComponent(u32);
+
+    impl Component for TestComponent {
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+    }
+
+    #[test]
+    fn test_object_storage_stale_component_id() {
+        let mut storage = ObjectStorage::new();
+        let object_id = storage.add(vec![]);
+        let component_id1 = storage.add_component(object_id, TestComponent(1)).unwrap();
+        assert_eq!(storage.component_owner(component_id1), Some(object_id));
+
+        storage.remove_component(object_id, component_id1);
+        assert_eq!(storage.component_owner(component_id1), None);
+
+        let component_id2 = storage.add_component(object_id, TestComponent(2)).unwrap();
+        assert_ne!(component_id1, component_id2);
+
+        let object = storage.get(object_id).unwrap();
+        assert!(object
+            .find_component_by_id::<TestComponent>(component_id1)
+            .is_none());
+        assert_eq!(
+            object
+                .find_component_by_id::<TestComponent>(component_id2)
+                .map(|component| component.0),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_object_storage_remove_releases_component_ids() {
+        let mut storage = ObjectStorage::new();
+        let object_id = storage.add(vec![]);
+        let component_id = storage.add_component(object_id, TestComponent(1)).unwrap();
+
+        storage.remove(object_id);
+        assert_eq!(storage.component_owner(component_id), None);
+    }
+}
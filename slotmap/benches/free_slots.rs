@@ -0,0 +1,128 @@
+//! Compares the free-slot tracking designs of `SlotMap`.
+//!
+//! `legacy` replays the original `BTreeMap<u32, Bitmap>` free list, which dropped full bitmaps
+//! lazily on every insertion. Run with `cargo bench -p slotmap`.
+
+use slotmap::{FreeSlotPolicy, HierarchicalBitset, SlotMap, SlotMapConfig, SlotMapId};
+use std::{collections::BTreeMap, hint::black_box, time::Instant};
+
+const LIVE: u32 = 10_000;
+const ROUNDS: u32 = 1_000_000;
+
+/// The free list `SlotMap` used before the hierarchical bitset and the intrusive list.
+#[derive(Default)]
+struct LegacyFreeSlots {
+    freed_bitmaps: BTreeMap<u32, u64>,
+}
+
+impl LegacyFreeSlots {
+    fn allocate(&mut self) -> Option<u32> {
+        while let Some(mut entry) = self.freed_bitmaps.first_entry() {
+            let bits = entry.get_mut();
+
+            if *bits == 0 {
+                entry.remove();
+                continue;
+            }
+
+            let local_index = bits.trailing_zeros();
+            *bits &= !(1 << local_index);
+            return Some(*entry.key() * u64::BITS + local_index);
+        }
+
+        None
+    }
+
+    fn free(&mut self, index: u32) {
+        *self.freed_bitmaps.entry(index / u64::BITS).or_default() |= 1 << (index % u64::BITS);
+    }
+}
+
+/// Small deterministic xorshift generator, so every design sees the same sequence.
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, bound: u32) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as u32
+    }
+}
+
+fn bench(name: &str, mut f: impl FnMut() -> u64) {
+    // Warm up caches and the allocator before measuring.
+    black_box(f());
+
+    let start = Instant::now();
+    black_box(f());
+    let elapsed = start.elapsed();
+
+    println!(
+        "{name:<40} {:>8.2} ns/op",
+        elapsed.as_nanos() as f64 / ROUNDS as f64
+    );
+}
+
+/// Frees a random slot and immediately allocates one, keeping `LIVE` slots in use.
+fn bench_allocators() {
+    bench("allocator/legacy btree bitmaps", || {
+        let mut free_slots = LegacyFreeSlots::default();
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+        let mut checksum = 0;
+
+        for _ in 0..ROUNDS {
+            free_slots.free(rng.below(LIVE));
+            checksum += free_slots.allocate().unwrap() as u64;
+        }
+
+        checksum
+    });
+
+    bench("allocator/hierarchical bitset", || {
+        let mut free_slots = HierarchicalBitset::new();
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+        let mut checksum = 0;
+
+        for _ in 0..ROUNDS {
+            free_slots.insert(rng.below(LIVE));
+            let index = free_slots.first().unwrap();
+            free_slots.remove(index);
+            checksum += index as u64;
+        }
+
+        checksum
+    });
+}
+
+/// Spawns and despawns short-lived entries the way a projectile system would.
+fn bench_slot_map(name: &str, free_slot_policy: FreeSlotPolicy) {
+    bench(name, || {
+        let mut map = SlotMap::with_config(SlotMapConfig {
+            free_slot_policy,
+            ..Default::default()
+        });
+        let mut ids = (0..LIVE)
+            .map(|v| map.add(v as u64))
+            .collect::<Vec<SlotMapId>>();
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+        let mut checksum = 0;
+
+        for round in 0..ROUNDS {
+            let index = rng.below(LIVE) as usize;
+            checksum += map.remove(ids[index]).unwrap();
+            ids[index] = map.add(round as u64);
+        }
+
+        checksum
+    });
+}
+
+fn main() {
+    bench_allocators();
+    bench_slot_map("slotmap churn/lifo", FreeSlotPolicy::Lifo);
+    bench_slot_map(
+        "slotmap churn/lowest index first",
+        FreeSlotPolicy::LowestIndexFirst,
+    );
+}
@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+/// The number of items per chunk, and with that the most items a single write copies.
+pub(crate) const CHUNK_LEN: usize = 64;
+
+/// A vector stored in fixed-size chunks that clones share. Writing to a shared vector copies the
+/// chunk written to and the list of chunk pointers, never the other chunks.
+pub(crate) struct ChunkedVec<T> {
+    chunks: Arc<Vec<Arc<Vec<T>>>>,
+    len: usize,
+}
+
+impl<T> ChunkedVec<T> {
+    pub fn new() -> Self {
+        Self {
+            chunks: Arc::new(Vec::new()),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        (index < self.len).then(|| &self.chunks[index / CHUNK_LEN][index % CHUNK_LEN])
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
+        self.chunks.iter().flat_map(|chunk| chunk.iter())
+    }
+
+    /// Counts the chunks this vector shares with `other`.
+    #[cfg(test)]
+    pub fn shared_chunk_count(&self, other: &Self) -> usize {
+        self.chunks
+            .iter()
+            .zip(other.chunks.iter())
+            .filter(|(a, b)| Arc::ptr_eq(a, b))
+            .count()
+    }
+}
+
+impl<T> ChunkedVec<T>
+where
+    T: Clone,
+{
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.len {
+            return None;
+        }
+
+        let chunk = &mut Arc::make_mut(&mut self.chunks)[index / CHUNK_LEN];
+        Some(&mut Arc::make_mut(chunk)[index % CHUNK_LEN])
+    }
+
+    pub fn push(&mut self, item: T) {
+        let chunks = Arc::make_mut(&mut self.chunks);
+
+        if self.len.is_multiple_of(CHUNK_LEN) {
+            chunks.push(Arc::new(Vec::with_capacity(CHUNK_LEN)));
+        }
+
+        let last = chunks.last_mut().expect("a chunk was just ensured");
+        Arc::make_mut(last).push(item);
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let chunks = Arc::make_mut(&mut self.chunks);
+        let last = chunks.last_mut().expect("a non-empty vector has chunks");
+        let item = Arc::make_mut(last).pop();
+
+        if last.is_empty() {
+            chunks.pop();
+        }
+
+        self.len -= 1;
+        item
+    }
+
+    /// Removes the item at `index` by moving the last item into its place.
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "swap_remove index out of bounds");
+
+        let last = self.pop().expect("a non-empty vector has a last item");
+        match self.get_mut(index) {
+            Some(item) => std::mem::replace(item, last),
+            None => last,
+        }
+    }
+}
+
+impl<T> Clone for ChunkedVec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            chunks: self.chunks.clone(),
+            len: self.len,
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{Key, ParseSlotMapIdError, SlotMapId};
+use std::{
+    fmt::{self, Display},
+    num::NonZeroU16,
+    str::FromStr,
+};
+
+/// A 4-byte key with a 16-bit index and a 16-bit generation, for maps that never need more than
+/// 65536 slots and handles that have to be small, such as in network packets or GPU buffers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompactSlotMapId {
+    index: u16,
+    generation: NonZeroU16,
+}
+
+impl CompactSlotMapId {
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    pub fn generation(&self) -> NonZeroU16 {
+        self.generation
+    }
+
+    /// Packs the id into a `u32`, with the generation in the high 16 bits and the index in the
+    /// low 16 bits. The result is never zero.
+    pub fn to_bits(&self) -> u32 {
+        (self.generation.get() as u32) << 16 | self.index as u32
+    }
+
+    /// Rebuilds an id packed by [`CompactSlotMapId::to_bits`], returning `None` if the
+    /// generation bits are zero.
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        let generation = NonZeroU16::new((bits >> 16) as u16)?;
+        Some(Self {
+            index: bits as u16,
+            generation,
+        })
+    }
+}
+
+/// # Panics
+///
+/// Panics if the index or the generation of `id` does not fit into 16 bits. Ids handed out by a
+/// map keyed by `CompactSlotMapId` always fit.
+impl From<SlotMapId> for CompactSlotMapId {
+    fn from(id: SlotMapId) -> Self {
+        match (
+            u16::try_from(id.index()),
+            NonZeroU16::try_from(id.generation()),
+        ) {
+            (Ok(index), Ok(generation)) => Self { index, generation },
+            _ => panic!("{id} does not fit into a CompactSlotMapId"),
+        }
+    }
+}
+
+impl Key for CompactSlotMapId {
+    const INDEX_BITS: u32 = 16;
+    const GENERATION_BITS: u32 = 16;
+
+    type BitsetWord = u32;
+
+    fn slot_map_id(&self) -> SlotMapId {
+        SlotMapId::new(self.index as u32, self.generation.into())
+    }
+}
+
+/// Formats the id as `{index}v{generation}`, e.g. `3v1`, like [`SlotMapId`].
+impl Display for CompactSlotMapId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+impl FromStr for CompactSlotMapId {
+    type Err = ParseSlotMapIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, generation) = s.split_once('v').ok_or(ParseSlotMapIdError)?;
+        let index = index.parse().map_err(|_| ParseSlotMapIdError)?;
+        let generation = generation.parse().map_err(|_| ParseSlotMapIdError)?;
+        Ok(Self { index, generation })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GenerationPolicy, SlotMap};
+    use std::num::NonZeroU32;
+
+    #[test]
+    fn test_compact_id_conversions() {
+        let id = CompactSlotMapId::from(SlotMapId::new(7, NonZeroU32::new(3).unwrap()));
+        assert_eq!(std::mem::size_of::<CompactSlotMapId>(), 4);
+        assert_eq!(id.to_bits(), 3 << 16 | 7);
+        assert_eq!(Key::to_bits(&id), 3 << 16 | 7);
+        assert_eq!(CompactSlotMapId::from_bits(id.to_bits()), Some(id));
+        assert_eq!(<CompactSlotMapId as Key>::from_bits(1 << 32), None);
+        assert_eq!(id.to_string(), "7v3");
+        assert_eq!("7v3".parse(), Ok(id));
+        assert_eq!(
+            "70000v3".parse::<CompactSlotMapId>(),
+            Err(ParseSlotMapIdError)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit into a CompactSlotMapId")]
+    fn test_compact_id_from_wide_id() {
+        let _ = CompactSlotMapId::from(SlotMapId::new(1 << 16, NonZeroU32::MIN));
+    }
+
+    #[test]
+    fn test_compact_id_generation_policy() {
+        let mut map = SlotMap::<CompactSlotMapId, u32>::with_key_and_config(crate::SlotMapConfig {
+            generation_policy: GenerationPolicy::Retire,
+            ..Default::default()
+        });
+
+        let mut id = map.add(0);
+        for value in 1..u16::MAX as u32 {
+            map.remove(id);
+            id = map.add(value);
+            assert_eq!(id.index(), 0);
+        }
+        assert_eq!(id.generation().get(), u16::MAX);
+
+        // The slot is exhausted at the largest 16-bit generation and gets retired.
+        map.remove(id);
+        assert_eq!(map.retired_slot_count(), 1);
+        assert_eq!(map.add(0).index(), 1);
+        map.debug_validate();
+    }
+
+    #[test]
+    fn test_compact_id_lowest_index_first() {
+        let mut map = SlotMap::<CompactSlotMapId, u32>::with_key_and_config(crate::SlotMapConfig {
+            free_slot_policy: crate::FreeSlotPolicy::LowestIndexFirst,
+            ..Default::default()
+        });
+        let ids = (0..100).map(|value| map.add(value)).collect::<Vec<_>>();
+
+        map.remove(ids[90]);
+        map.remove(ids[40]);
+        assert_eq!(map.add(100).index(), 40);
+        assert_eq!(map.add(101).index(), 90);
+        map.debug_validate();
+
+        // The free slot bitset uses the 32-bit word the key packs into.
+        let crate::FreeSlots::LowestIndexFirst(bitset) = &map.free_slots else {
+            panic!("the map tracks free slots in a bitset");
+        };
+        let _: &crate::HierarchicalBitset<u32> = bitset;
+    }
+
+    #[test]
+    #[should_panic(expected = "ran out of slot indices")]
+    fn test_compact_id_index_limit() {
+        let mut map = SlotMap::<CompactSlotMapId, ()>::with_key();
+        for _ in 0..=u16::MAX as u32 + 1 {
+            map.add(());
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use crate::{key::max_index, Key, SlotMap, SlotMapConfig, SlotMapId};
+use std::{
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+    thread,
+};
+
+/// A [`SlotMap`] that can be shared between threads and changed through `&self`.
+///
+/// Entries are spread over independently locked shards, so threads only contend when they touch
+/// the same shard. Keys use the regular [`SlotMapId`] format: the slot index modulo the shard count
+/// selects the shard, and the generation keeps its usual meaning within the shard.
+pub struct ConcurrentSlotMap<K, T>
+where
+    K: Key,
+{
+    shards: Box<[RwLock<SlotMap<K, T>>]>,
+    next_shard: AtomicUsize,
+    key: PhantomData<fn() -> K>,
+}
+
+impl<T> ConcurrentSlotMap<SlotMapId, T> {
+    pub fn new() -> Self {
+        Self::with_key()
+    }
+}
+
+impl<K, T> ConcurrentSlotMap<K, T>
+where
+    K: Key,
+{
+    /// Creates a map handing out keys of type `K`, with a few shards per available thread.
+    pub fn with_key() -> Self {
+        let parallelism = thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_key_and_config(parallelism * 4, SlotMapConfig::default())
+    }
+
+    /// Creates a map with `shard_count` shards, each following `config`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shard_count` is zero.
+    pub fn with_key_and_config(shard_count: usize, config: SlotMapConfig) -> Self {
+        assert!(
+            shard_count > 0,
+            "a concurrent slot map needs at least one shard"
+        );
+        let shard_count = shard_count.min(u32::MAX as usize);
+
+        Self {
+            shards: (0..shard_count)
+                .map(|_| RwLock::new(SlotMap::with_key_and_config(config)))
+                .collect(),
+            next_shard: AtomicUsize::new(0),
+            key: PhantomData,
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Counts the entries of every shard. Concurrent changes may or may not be included.
+    pub fn len(&self) -> usize {
+        (0..self.shards.len()).map(|s| self.read(s).len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..self.shards.len()).all(|s| self.read(s).is_empty())
+    }
+
+    pub fn contains_key(&self, key: K) -> bool {
+        self.with(key, |_| ()).is_some()
+    }
+
+    /// Returns a clone of the value. Use [`with`](Self::with) to avoid the clone.
+    pub fn get(&self, key: K) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.with(key, T::clone)
+    }
+
+    /// Calls `f` with the value while its shard is read-locked.
+    pub fn with<R>(&self, key: K, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let (shard, id) = self.split(key);
+        self.read(shard).get(id).map(f)
+    }
+
+    /// Calls `f` with the value while its shard is write-locked.
+    pub fn with_mut<R>(&self, key: K, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let (shard, id) = self.split(key);
+        self.write(shard).get_mut(id).map(f)
+    }
+
+    pub fn add(&self, item: T) -> K {
+        self.insert_with_key(|_| item)
+    }
+
+    /// Inserts a value built from its own key. `f` runs while the target shard is write-locked, so
+    /// it must not access this map.
+    pub fn insert_with_key(&self, f: impl FnOnce(K) -> T) -> K {
+        let shard = self.next_shard.fetch_add(1, Ordering::Relaxed) % self.shards.len();
+        let mut map = self.write(shard);
+        let entry = map.vacant_entry();
+        let key = self.join(shard, entry.id());
+        entry.insert(f(key));
+        key
+    }
+
+    pub fn remove(&self, key: K) -> Option<T> {
+        let (shard, id) = self.split(key);
+        self.write(shard).remove(id)
+    }
+
+    /// Removes every entry. Entries added concurrently to an already cleared shard are kept.
+    pub fn clear(&self) {
+        for shard in 0..self.shards.len() {
+            self.write(shard).clear();
+        }
+    }
+
+    /// Checks the internal invariants of every shard, panicking on the first violation.
+    pub fn debug_validate(&self) {
+        for shard in 0..self.shards.len() {
+            self.read(shard).debug_validate();
+        }
+    }
+
+    /// Translates a key of this map into its shard and the key within that shard.
+    fn split(&self, key: K) -> (usize, K) {
+        let id = key.slot_map_id();
+        let shard_count = self.shards.len() as u32;
+        let shard = id.index() % shard_count;
+        let id = SlotMapId::new(id.index() / shard_count, id.generation());
+        (shard as usize, K::from(id))
+    }
+
+    fn join(&self, shard: usize, key: K) -> K {
+        let id = key.slot_map_id();
+        let index = (id.index() as u64) * self.shards.len() as u64 + shard as u64;
+        let index = u32::try_from(index)
+            .ok()
+            .filter(|&index| index <= max_index::<K>())
+            .expect("concurrent slot map ran out of slot indices");
+        K::from(SlotMapId::new(index, id.generation()))
+    }
+
+    // A panic while a shard is locked either happens before the shard is changed, like the
+    // generation exhaustion panic of `remove`, or inside a user callback that only sees a value,
+    // so the shard itself is still consistent and poisoning can be ignored.
+    fn read(&self, shard: usize) -> RwLockReadGuard<'_, SlotMap<K, T>> {
+        self.shards[shard]
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self, shard: usize) -> RwLockWriteGuard<'_, SlotMap<K, T>> {
+        self.shards[shard]
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<K, T> Default for ConcurrentSlotMap<K, T>
+where
+    K: Key,
+{
+    fn default() -> Self {
+        Self::with_key()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GenerationPolicy;
+    use std::{collections::HashSet, sync::Mutex};
+
+    #[test]
+    fn test_concurrent_slotmap_add_get_remove() {
+        let map =
+            ConcurrentSlotMap::<SlotMapId, _>::with_key_and_config(3, SlotMapConfig::default());
+        let ids = (0..7).map(|v| map.add(v)).collect::<Vec<_>>();
+
+        assert_eq!(map.len(), 7);
+        assert_eq!(
+            ids.iter().map(|id| id.index()).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 4, 5, 6]
+        );
+        for (v, id) in ids.iter().enumerate() {
+            assert_eq!(map.get(*id), Some(v));
+        }
+
+        assert_eq!(map.remove(ids[4]), Some(4));
+        assert_eq!(map.remove(ids[4]), None);
+        assert!(!map.contains_key(ids[4]));
+        assert_eq!(map.with_mut(ids[5], |v| std::mem::replace(v, 50)), Some(5));
+        assert_eq!(map.with(ids[5], |v| *v + 1), Some(51));
+
+        // The freed slot is reused with a new generation in the same shard.
+        let reused = (0..3).map(|v| map.add(v)).find(|id| id.index() == 4);
+        let reused = reused.unwrap();
+        assert_ne!(reused, ids[4]);
+        assert_eq!(map.get(ids[4]), None);
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get(ids[0]), None);
+    }
+
+    #[test]
+    fn test_concurrent_slotmap_threads() {
+        let map = ConcurrentSlotMap::<SlotMapId, _>::with_key_and_config(
+            4,
+            SlotMapConfig {
+                generation_policy: GenerationPolicy::Panic,
+                ..Default::default()
+            },
+        );
+        let kept = Mutex::new(Vec::new());
+
+        thread::scope(|scope| {
+            for t in 0..8u32 {
+                let (map, kept) = (&map, &kept);
+                scope.spawn(move || {
+                    let mut removed = Vec::new();
+                    for i in 0..1000u32 {
+                        let value = t * 1000 + i;
+                        let id = map.add(value);
+                        assert_eq!(map.get(id), Some(value));
+
+                        if i % 2 == 0 {
+                            assert_eq!(map.remove(id), Some(value));
+                            removed.push(id);
+                        } else {
+                            kept.lock().unwrap().push((id, value));
+                        }
+                    }
+
+                    for id in removed {
+                        assert_eq!(map.get(id), None);
+                    }
+                });
+            }
+        });
+
+        let kept = kept.into_inner().unwrap();
+        assert_eq!(map.len(), kept.len());
+        assert_eq!(
+            kept.iter().map(|(id, _)| *id).collect::<HashSet<_>>().len(),
+            kept.len()
+        );
+        for (id, value) in kept {
+            assert_eq!(map.get(id), Some(value));
+        }
+
+        map.debug_validate();
+    }
+}
@@ -0,0 +1,9 @@
+use crate::{FreeSlotPolicy, GenerationPolicy};
+
+/// Construction-time options of a [`SlotMap`](crate::SlotMap).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SlotMapConfig {
+    pub generation_policy: GenerationPolicy,
+    pub free_slot_policy: FreeSlotPolicy,
+}
@@ -0,0 +1,30 @@
+use crate::{Key, SlotMap, SlotMapId};
+
+/// A reserved but not yet filled slot of a [`SlotMap`], obtained from
+/// [`SlotMap::vacant_entry`].
+pub struct VacantEntry<'a, K, T>
+where
+    K: Key,
+{
+    map: &'a mut SlotMap<K, T>,
+    id: SlotMapId,
+}
+
+impl<'a, K, T> VacantEntry<'a, K, T>
+where
+    K: Key,
+{
+    pub(crate) fn new(map: &'a mut SlotMap<K, T>, id: SlotMapId) -> Self {
+        Self { map, id }
+    }
+
+    /// Returns the key the value will have once inserted.
+    pub fn id(&self) -> K {
+        K::from(self.id)
+    }
+
+    pub fn insert(self, item: T) -> K {
+        self.map.occupy_slot(self.id, item);
+        K::from(self.id)
+    }
+}
@@ -0,0 +1,12 @@
+/// Decides which freed slot a [`SlotMap`](crate::SlotMap) reuses on insertion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FreeSlotPolicy {
+    /// The most recently freed slot is reused first. Freed slots form an intrusive list, so both
+    /// allocating and freeing are `O(1)` without any extra memory.
+    #[default]
+    Lifo,
+    /// The lowest freed slot is reused first, keeping the slots packed towards the front. Freed
+    /// slots are tracked in a [`HierarchicalBitset`](crate::HierarchicalBitset).
+    LowestIndexFirst,
+}
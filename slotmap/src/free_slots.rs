@@ -0,0 +1,107 @@
+use crate::{BitsetWord, FreeSlotPolicy, HierarchicalBitset};
+
+/// Marks the end of the intrusive free list.
+const NIL: u32 = u32::MAX;
+
+/// The set of freed slots of a [`SlotMap`](crate::SlotMap).
+///
+/// Under [`FreeSlotPolicy::Lifo`] the list is threaded through the slot element indices of the
+/// freed slots themselves, which are meaningless while a slot is free. Every method that walks
+/// the list takes those links as a parameter.
+#[derive(Clone)]
+pub(crate) enum FreeSlots<W> {
+    Lifo { head: u32, len: usize },
+    LowestIndexFirst(HierarchicalBitset<W>),
+}
+
+impl<W> FreeSlots<W>
+where
+    W: BitsetWord,
+{
+    pub fn new(policy: FreeSlotPolicy) -> Self {
+        match policy {
+            FreeSlotPolicy::Lifo => Self::Lifo { head: NIL, len: 0 },
+            FreeSlotPolicy::LowestIndexFirst => {
+                Self::LowestIndexFirst(HierarchicalBitset::with_word())
+            }
+        }
+    }
+
+    pub fn policy(&self) -> FreeSlotPolicy {
+        match self {
+            Self::Lifo { .. } => FreeSlotPolicy::Lifo,
+            Self::LowestIndexFirst(_) => FreeSlotPolicy::LowestIndexFirst,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Lifo { len, .. } => *len,
+            Self::LowestIndexFirst(bitset) => bitset.len(),
+        }
+    }
+
+    /// Returns the slot the next insertion will reuse.
+    pub fn first(&self) -> Option<u32> {
+        match self {
+            Self::Lifo { head, .. } => (*head != NIL).then_some(*head),
+            Self::LowestIndexFirst(bitset) => bitset.first(),
+        }
+    }
+
+    pub fn push(&mut self, slot_index: u32, links: &mut [u32]) {
+        match self {
+            Self::Lifo { head, len } => {
+                links[slot_index as usize] = *head;
+                *head = slot_index;
+                *len += 1;
+            }
+            Self::LowestIndexFirst(bitset) => {
+                bitset.insert(slot_index);
+            }
+        }
+    }
+
+    /// Takes the slot returned by [`FreeSlots::first`] out of the set.
+    pub fn pop_first(&mut self, links: &[u32]) -> Option<u32> {
+        match self {
+            Self::Lifo { head, len } => {
+                if *head == NIL {
+                    return None;
+                }
+
+                let slot_index = *head;
+                *head = links[slot_index as usize];
+                *len -= 1;
+                Some(slot_index)
+            }
+            Self::LowestIndexFirst(bitset) => {
+                let slot_index = bitset.first()?;
+                bitset.remove(slot_index);
+                Some(slot_index)
+            }
+        }
+    }
+
+    /// Collects the freed slots in the order they will be reused.
+    pub fn to_vec(&self, links: &[u32]) -> Vec<u32> {
+        match self {
+            Self::Lifo { head, len } => {
+                let mut slot_indices = Vec::with_capacity(*len);
+                let mut slot_index = *head;
+
+                while slot_index != NIL {
+                    assert!(
+                        slot_indices.len() < *len,
+                        "free list is longer than its recorded length"
+                    );
+                    slot_indices.push(slot_index);
+                    slot_index = links[slot_index as usize];
+                }
+
+                slot_indices
+            }
+            Self::LowestIndexFirst(bitset) => bitset.iter().collect(),
+        }
+    }
+}
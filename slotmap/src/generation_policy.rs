@@ -0,0 +1,19 @@
+/// Decides what happens to a slot whose generation counter is exhausted, i.e. when an id with the
+/// largest generation its key can store, `2^GENERATION_BITS - 1` (see
+/// [`Key::GENERATION_BITS`](crate::Key::GENERATION_BITS)), is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GenerationPolicy {
+    /// The generation wraps back to `1`. Ids that are `2^GENERATION_BITS - 1` generations old
+    /// become valid again once the slot is reused.
+    #[default]
+    Wrap,
+    /// The slot is never reused again, so no id can ever be resurrected. Retired slots cost
+    /// their bookkeeping memory for the lifetime of the map.
+    Retire,
+    /// [`SlotMap::remove`](crate::SlotMap::remove) and
+    /// [`SlotMap::remove_stable`](crate::SlotMap::remove_stable) panic, leaving the map untouched.
+    /// Bulk removals, i.e. `clear`, `retain` and `drain`, retire the slot instead, as a panic
+    /// there would leave the map half emptied.
+    Panic,
+}
@@ -0,0 +1,311 @@
+use std::{
+    fmt::Debug,
+    ops::{BitAnd, BitAndAssign, BitOrAssign, Not},
+};
+
+/// An unsigned integer a [`HierarchicalBitset`] stores its bits in.
+pub trait BitsetWord:
+    Debug
+    + Clone
+    + Copy
+    + Default
+    + Eq
+    + BitAnd<Output = Self>
+    + BitAndAssign
+    + BitOrAssign
+    + Not<Output = Self>
+{
+    const BITS: u32;
+    const ZERO: Self;
+
+    /// Returns the word with only bit `index` set.
+    fn bit(index: u32) -> Self;
+
+    fn trailing_zeros(self) -> u32;
+
+    fn count_ones(self) -> u32;
+}
+
+macro_rules! impl_bitset_word {
+    ($($word:ty),*) => {$(
+        impl BitsetWord for $word {
+            const BITS: u32 = <$word>::BITS;
+            const ZERO: Self = 0;
+
+            fn bit(index: u32) -> Self {
+                1 << index
+            }
+
+            fn trailing_zeros(self) -> u32 {
+                <$word>::trailing_zeros(self)
+            }
+
+            fn count_ones(self) -> u32 {
+                <$word>::count_ones(self)
+            }
+        }
+    )*};
+}
+
+impl_bitset_word!(u8, u16, u32, u64, u128);
+
+/// A growable set of `u32` indices that finds its lowest member in `O(log_w n)`, for words of
+/// `w` bits.
+///
+/// The first level holds one bit per index. Every level above summarizes the level below with
+/// one bit per word, set when that word has any bit set, up to a single top-level word. A
+/// [`SlotMap`](crate::SlotMap) picks the word from its key, see
+/// [`Key::BitsetWord`](crate::Key::BitsetWord).
+#[derive(Debug, Clone, Default)]
+pub struct HierarchicalBitset<W = u64> {
+    levels: Vec<Vec<W>>,
+    len: usize,
+}
+
+impl HierarchicalBitset {
+    pub fn new() -> Self {
+        Self::with_word()
+    }
+}
+
+impl<W> HierarchicalBitset<W>
+where
+    W: BitsetWord,
+{
+    pub const BITS_PER_WORD: u32 = W::BITS;
+
+    pub fn with_word() -> Self {
+        Self {
+            levels: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of indices in the set.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains(&self, index: u32) -> bool {
+        let word_index = (index / Self::BITS_PER_WORD) as usize;
+        let bit = W::bit(index % Self::BITS_PER_WORD);
+
+        self.levels
+            .first()
+            .and_then(|words| words.get(word_index))
+            .is_some_and(|&word| word & bit != W::ZERO)
+    }
+
+    /// Adds `index` to the set, returning `false` if it was already present.
+    pub fn insert(&mut self, index: u32) -> bool {
+        if self.contains(index) {
+            return false;
+        }
+
+        let word_index = (index / Self::BITS_PER_WORD) as usize;
+        if self.levels.first().map_or(0, |words| words.len()) <= word_index {
+            self.grow(word_index + 1);
+        }
+
+        let mut index = index as usize;
+        for words in &mut self.levels {
+            let word = &mut words[index / Self::BITS_PER_WORD as usize];
+            let was_empty = *word == W::ZERO;
+            *word |= W::bit((index % Self::BITS_PER_WORD as usize) as u32);
+
+            // Upper levels already summarize a word that had other bits set.
+            if !was_empty {
+                break;
+            }
+
+            index /= Self::BITS_PER_WORD as usize;
+        }
+
+        self.len += 1;
+        true
+    }
+
+    /// Removes `index` from the set, returning `false` if it was not present.
+    pub fn remove(&mut self, index: u32) -> bool {
+        if !self.contains(index) {
+            return false;
+        }
+
+        let mut index = index as usize;
+        for words in &mut self.levels {
+            let word = &mut words[index / Self::BITS_PER_WORD as usize];
+            *word &= !W::bit((index % Self::BITS_PER_WORD as usize) as u32);
+
+            if *word != W::ZERO {
+                break;
+            }
+
+            index /= Self::BITS_PER_WORD as usize;
+        }
+
+        self.len -= 1;
+        true
+    }
+
+    /// Returns the lowest index in the set.
+    pub fn first(&self) -> Option<u32> {
+        let top = self.levels.last()?;
+        if top[0] == W::ZERO {
+            return None;
+        }
+
+        let mut index = 0;
+        for words in self.levels.iter().rev() {
+            let word = words[index];
+            index = index * Self::BITS_PER_WORD as usize + word.trailing_zeros() as usize;
+        }
+
+        Some(index as u32)
+    }
+
+    /// Iterates over the indices in the set in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.levels
+            .first()
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .flat_map(|(word_index, &word)| {
+                (0..Self::BITS_PER_WORD)
+                    .filter(move |&bit| word & W::bit(bit) != W::ZERO)
+                    .map(move |bit| word_index as u32 * Self::BITS_PER_WORD + bit)
+            })
+    }
+
+    pub fn clear(&mut self) {
+        self.levels.clear();
+        self.len = 0;
+    }
+
+    /// Checks that every upper level exactly summarizes the level below it.
+    pub fn debug_validate(&self) {
+        let mut len = 0;
+
+        for (level, words) in self.levels.iter().enumerate() {
+            if level == 0 {
+                len = words.iter().map(|word| word.count_ones() as usize).sum();
+                continue;
+            }
+
+            let lower = &self.levels[level - 1];
+            assert_eq!(
+                words.len(),
+                lower.len().div_ceil(Self::BITS_PER_WORD as usize),
+                "level {level} has the wrong number of words"
+            );
+
+            for (word_index, &word) in lower.iter().enumerate() {
+                let bit = W::bit((word_index % Self::BITS_PER_WORD as usize) as u32);
+                let summary = words[word_index / Self::BITS_PER_WORD as usize] & bit != W::ZERO;
+                assert_eq!(
+                    summary,
+                    word != W::ZERO,
+                    "level {level} does not summarize word {word_index} of the level below"
+                );
+            }
+        }
+
+        if let Some(top) = self.levels.last() {
+            assert_eq!(top.len(), 1, "top level must be a single word");
+        }
+
+        assert_eq!(self.len, len, "cached length is out of sync");
+    }
+
+    fn grow(&mut self, min_word_count: usize) {
+        let word_count = min_word_count.max(self.levels.first().map_or(0, |words| words.len() * 2));
+        let mut words = self.levels.first().cloned().unwrap_or_default();
+        words.resize(word_count, W::ZERO);
+
+        // Growing is amortized by doubling, so the upper levels are simply rebuilt.
+        self.levels.clear();
+        self.levels.push(words);
+
+        while self.levels.last().unwrap().len() > 1 {
+            let lower = self.levels.last().unwrap();
+            let mut upper = vec![W::ZERO; lower.len().div_ceil(Self::BITS_PER_WORD as usize)];
+
+            for (word_index, &word) in lower.iter().enumerate() {
+                if word != W::ZERO {
+                    upper[word_index / Self::BITS_PER_WORD as usize] |=
+                        W::bit((word_index % Self::BITS_PER_WORD as usize) as u32);
+                }
+            }
+
+            self.levels.push(upper);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hierarchical_bitset_first() {
+        let mut bitset = HierarchicalBitset::new();
+        assert_eq!(bitset.first(), None);
+
+        assert!(bitset.insert(100_000));
+        assert!(bitset.insert(70));
+        assert!(!bitset.insert(70));
+        assert_eq!(bitset.first(), Some(70));
+        assert_eq!(bitset.len(), 2);
+
+        assert!(bitset.remove(70));
+        assert!(!bitset.remove(70));
+        assert_eq!(bitset.first(), Some(100_000));
+
+        assert!(bitset.insert(3));
+        assert_eq!(bitset.first(), Some(3));
+        assert_eq!(bitset.iter().collect::<Vec<_>>(), vec![3, 100_000]);
+        bitset.debug_validate();
+    }
+
+    #[test]
+    fn test_hierarchical_bitset_sequential() {
+        let mut bitset = HierarchicalBitset::new();
+
+        for index in (0..10_000).rev() {
+            bitset.insert(index);
+        }
+        bitset.debug_validate();
+
+        for index in 0..10_000 {
+            assert_eq!(bitset.first(), Some(index));
+            bitset.remove(index);
+        }
+
+        assert!(bitset.is_empty());
+        assert_eq!(bitset.first(), None);
+        bitset.debug_validate();
+    }
+
+    #[test]
+    fn test_hierarchical_bitset_narrow_words() {
+        let mut bitset = HierarchicalBitset::<u16>::with_word();
+
+        for index in (0..5_000).step_by(7) {
+            bitset.insert(index);
+        }
+        bitset.debug_validate();
+        assert_eq!(bitset.levels.len(), 4);
+
+        for index in (0..5_000).step_by(7) {
+            assert_eq!(bitset.first(), Some(index));
+            assert!(bitset.remove(index));
+        }
+
+        assert_eq!(bitset.first(), None);
+        bitset.debug_validate();
+    }
+}
@@ -1,6 +1,12 @@
-use std::num::NonZeroU32;
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    num::NonZeroU32,
+    str::FromStr,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SlotMapId {
     index: u32,
     generation: NonZeroU32,
@@ -18,4 +24,75 @@ impl SlotMapId {
     pub fn generation(&self) -> NonZeroU32 {
         self.generation
     }
+
+    /// Packs the id into a `u64`, with the generation in the high 32 bits and the index in the
+    /// low 32 bits. The result is never zero.
+    pub fn to_bits(&self) -> u64 {
+        (self.generation.get() as u64) << 32 | self.index as u64
+    }
+
+    /// Rebuilds an id packed by [`SlotMapId::to_bits`], returning `None` if the generation bits
+    /// are zero.
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        let generation = NonZeroU32::new((bits >> 32) as u32)?;
+        Some(Self::new(bits as u32, generation))
+    }
+}
+
+/// Formats the id as `{index}v{generation}`, e.g. `3v1`.
+impl Display for SlotMapId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+impl FromStr for SlotMapId {
+    type Err = ParseSlotMapIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, generation) = s.split_once('v').ok_or(ParseSlotMapIdError)?;
+        let index = index.parse().map_err(|_| ParseSlotMapIdError)?;
+        let generation = generation.parse().map_err(|_| ParseSlotMapIdError)?;
+        Ok(Self::new(index, generation))
+    }
+}
+
+/// The error returned when a string is not a valid `{index}v{generation}` id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseSlotMapIdError;
+
+impl Display for ParseSlotMapIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid id; expected `{{index}}v{{generation}}`")
+    }
+}
+
+impl Error for ParseSlotMapIdError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slotmap_id_bits() {
+        let id = SlotMapId::new(7, NonZeroU32::new(3).unwrap());
+        assert_eq!(id.to_bits(), 3 << 32 | 7);
+        assert_eq!(SlotMapId::from_bits(id.to_bits()), Some(id));
+
+        let id = SlotMapId::new(u32::MAX, NonZeroU32::MAX);
+        assert_eq!(SlotMapId::from_bits(id.to_bits()), Some(id));
+        assert_eq!(SlotMapId::from_bits(7), None);
+    }
+
+    #[test]
+    fn test_slotmap_id_display_from_str() {
+        let id = SlotMapId::new(7, NonZeroU32::new(3).unwrap());
+        assert_eq!(id.to_string(), "7v3");
+        assert_eq!("7v3".parse(), Ok(id));
+
+        assert_eq!("7v0".parse::<SlotMapId>(), Err(ParseSlotMapIdError));
+        assert_eq!("7".parse::<SlotMapId>(), Err(ParseSlotMapIdError));
+        assert_eq!("v3".parse::<SlotMapId>(), Err(ParseSlotMapIdError));
+        assert_eq!("-1v3".parse::<SlotMapId>(), Err(ParseSlotMapIdError));
+    }
 }
@@ -0,0 +1,296 @@
+use crate::{Key, SlotMap, SlotMapId};
+use std::{iter::FusedIterator, marker::PhantomData, num::NonZeroU32, slice, vec};
+
+pub struct Iter<'a, K, T> {
+    elements: slice::Iter<'a, T>,
+    element_slot_indices: slice::Iter<'a, u32>,
+    slot_generations: &'a [NonZeroU32],
+    key: PhantomData<fn() -> K>,
+}
+
+impl<'a, K, T> Iter<'a, K, T>
+where
+    K: Key,
+{
+    pub(crate) fn new(map: &'a SlotMap<K, T>) -> Self {
+        Self {
+            elements: map.elements.iter(),
+            element_slot_indices: map.element_slot_indices.iter(),
+            slot_generations: &map.slot_generations,
+            key: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, T> Iterator for Iter<'a, K, T>
+where
+    K: Key,
+{
+    type Item = (K, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let element = self.elements.next()?;
+        let slot_index = *self.element_slot_indices.next()?;
+        let id = SlotMapId::new(slot_index, self.slot_generations[slot_index as usize]);
+        Some((K::from(id), element))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.elements.size_hint()
+    }
+}
+
+impl<K, T> DoubleEndedIterator for Iter<'_, K, T>
+where
+    K: Key,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let element = self.elements.next_back()?;
+        let slot_index = *self.element_slot_indices.next_back()?;
+        let id = SlotMapId::new(slot_index, self.slot_generations[slot_index as usize]);
+        Some((K::from(id), element))
+    }
+}
+
+impl<K, T> ExactSizeIterator for Iter<'_, K, T> where K: Key {}
+
+impl<K, T> FusedIterator for Iter<'_, K, T> where K: Key {}
+
+pub struct IterMut<'a, K, T> {
+    elements: slice::IterMut<'a, T>,
+    element_slot_indices: slice::Iter<'a, u32>,
+    slot_generations: &'a [NonZeroU32],
+    key: PhantomData<fn() -> K>,
+}
+
+impl<'a, K, T> IterMut<'a, K, T>
+where
+    K: Key,
+{
+    pub(crate) fn new(map: &'a mut SlotMap<K, T>) -> Self {
+        Self {
+            elements: map.elements.iter_mut(),
+            element_slot_indices: map.element_slot_indices.iter(),
+            slot_generations: &map.slot_generations,
+            key: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, T> Iterator for IterMut<'a, K, T>
+where
+    K: Key,
+{
+    type Item = (K, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let element = self.elements.next()?;
+        let slot_index = *self.element_slot_indices.next()?;
+        let id = SlotMapId::new(slot_index, self.slot_generations[slot_index as usize]);
+        Some((K::from(id), element))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.elements.size_hint()
+    }
+}
+
+impl<K, T> DoubleEndedIterator for IterMut<'_, K, T>
+where
+    K: Key,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let element = self.elements.next_back()?;
+        let slot_index = *self.element_slot_indices.next_back()?;
+        let id = SlotMapId::new(slot_index, self.slot_generations[slot_index as usize]);
+        Some((K::from(id), element))
+    }
+}
+
+impl<K, T> ExactSizeIterator for IterMut<'_, K, T> where K: Key {}
+
+impl<K, T> FusedIterator for IterMut<'_, K, T> where K: Key {}
+
+pub struct Keys<'a, K, T> {
+    inner: Iter<'a, K, T>,
+}
+
+impl<'a, K, T> Keys<'a, K, T>
+where
+    K: Key,
+{
+    pub(crate) fn new(map: &'a SlotMap<K, T>) -> Self {
+        Self {
+            inner: Iter::new(map),
+        }
+    }
+}
+
+impl<K, T> Iterator for Keys<'_, K, T>
+where
+    K: Key,
+{
+    type Item = K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, T> DoubleEndedIterator for Keys<'_, K, T>
+where
+    K: Key,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<K, T> ExactSizeIterator for Keys<'_, K, T> where K: Key {}
+
+impl<K, T> FusedIterator for Keys<'_, K, T> where K: Key {}
+
+/// Yields every entry of the map, removing it. Entries that are not consumed are removed when the
+/// iterator is dropped.
+pub struct Drain<'a, K, T>
+where
+    K: Key,
+{
+    map: &'a mut SlotMap<K, T>,
+}
+
+impl<'a, K, T> Drain<'a, K, T>
+where
+    K: Key,
+{
+    pub(crate) fn new(map: &'a mut SlotMap<K, T>) -> Self {
+        Self { map }
+    }
+}
+
+impl<K, T> Iterator for Drain<'_, K, T>
+where
+    K: Key,
+{
+    type Item = (K, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Draining from the back keeps every removal a plain pop of the dense storage.
+        let slot_index = *self.map.element_slot_indices.last()?;
+        let id = SlotMapId::new(slot_index, self.map.slot_generations[slot_index as usize]);
+        let element_index = self.map.elements.len() - 1;
+        Some((K::from(id), self.map.swap_remove_element(element_index, id)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.map.len(), Some(self.map.len()))
+    }
+}
+
+impl<K, T> ExactSizeIterator for Drain<'_, K, T> where K: Key {}
+
+impl<K, T> FusedIterator for Drain<'_, K, T> where K: Key {}
+
+impl<K, T> Drop for Drain<'_, K, T>
+where
+    K: Key,
+{
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+pub struct IntoIter<K, T> {
+    elements: vec::IntoIter<T>,
+    element_slot_indices: vec::IntoIter<u32>,
+    slot_generations: Vec<NonZeroU32>,
+    key: PhantomData<fn() -> K>,
+}
+
+impl<K, T> IntoIter<K, T>
+where
+    K: Key,
+{
+    pub(crate) fn new(map: SlotMap<K, T>) -> Self {
+        Self {
+            elements: map.elements.into_iter(),
+            element_slot_indices: map.element_slot_indices.into_iter(),
+            slot_generations: map.slot_generations,
+            key: PhantomData,
+        }
+    }
+}
+
+impl<K, T> Iterator for IntoIter<K, T>
+where
+    K: Key,
+{
+    type Item = (K, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let element = self.elements.next()?;
+        let slot_index = self.element_slot_indices.next()?;
+        let id = SlotMapId::new(slot_index, self.slot_generations[slot_index as usize]);
+        Some((K::from(id), element))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.elements.size_hint()
+    }
+}
+
+impl<K, T> DoubleEndedIterator for IntoIter<K, T>
+where
+    K: Key,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let element = self.elements.next_back()?;
+        let slot_index = self.element_slot_indices.next_back()?;
+        let id = SlotMapId::new(slot_index, self.slot_generations[slot_index as usize]);
+        Some((K::from(id), element))
+    }
+}
+
+impl<K, T> ExactSizeIterator for IntoIter<K, T> where K: Key {}
+
+impl<K, T> FusedIterator for IntoIter<K, T> where K: Key {}
+
+impl<K, T> IntoIterator for SlotMap<K, T>
+where
+    K: Key,
+{
+    type Item = (K, T);
+    type IntoIter = IntoIter<K, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::new(self)
+    }
+}
+
+impl<'a, K, T> IntoIterator for &'a SlotMap<K, T>
+where
+    K: Key,
+{
+    type Item = (K, &'a T);
+    type IntoIter = Iter<'a, K, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Iter::new(self)
+    }
+}
+
+impl<'a, K, T> IntoIterator for &'a mut SlotMap<K, T>
+where
+    K: Key,
+{
+    type Item = (K, &'a mut T);
+    type IntoIter = IterMut<'a, K, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IterMut::new(self)
+    }
+}
@@ -0,0 +1,249 @@
+use crate::{BitsetWord, SlotMapId};
+use std::{fmt::Debug, hash::Hash, num::NonZeroU32};
+
+/// A typed handle into a [`SlotMap`](crate::SlotMap), backed by a [`SlotMapId`].
+///
+/// Giving every map its own key type turns passing a handle to the wrong map into a type error.
+/// Use [`new_key_type!`](crate::new_key_type) to declare one.
+///
+/// A key may store fewer bits than a [`SlotMapId`], see [`CompactSlotMapId`](crate::CompactSlotMapId).
+/// Maps never create more slots than [`Key::INDEX_BITS`] can address, and treat a slot whose
+/// generation reached the largest value of [`Key::GENERATION_BITS`] as exhausted, so that
+/// converting their ids into the key never loses information.
+pub trait Key: Debug + Clone + Copy + PartialEq + Eq + Hash + From<SlotMapId> {
+    /// The number of bits the key stores for the slot index, at most 32.
+    const INDEX_BITS: u32 = 32;
+
+    /// The number of bits the key stores for the generation, at most 32.
+    const GENERATION_BITS: u32 = 32;
+
+    /// The word of the [`HierarchicalBitset`](crate::HierarchicalBitset) that tracks free slots
+    /// under [`FreeSlotPolicy::LowestIndexFirst`](crate::FreeSlotPolicy::LowestIndexFirst),
+    /// usually the integer the key packs into.
+    type BitsetWord: BitsetWord;
+
+    fn slot_map_id(&self) -> SlotMapId;
+
+    /// Packs the key into a non-zero integer of `INDEX_BITS + GENERATION_BITS` bits, with the
+    /// generation above the index. For full width keys this is [`SlotMapId::to_bits`].
+    fn to_bits(&self) -> u64 {
+        let id = self.slot_map_id();
+        (id.generation().get() as u64) << Self::INDEX_BITS | id.index() as u64
+    }
+
+    /// Rebuilds a key packed by [`Key::to_bits`], returning `None` if the bits are out of range.
+    fn from_bits(bits: u64) -> Option<Self> {
+        let generation = bits >> Self::INDEX_BITS;
+        let index = (bits & max_index::<Self>() as u64) as u32;
+
+        if generation > max_generation::<Self>().get() as u64 {
+            return None;
+        }
+
+        let generation = NonZeroU32::new(generation as u32)?;
+        Some(Self::from(SlotMapId::new(index, generation)))
+    }
+}
+
+/// Returns the largest slot index that fits into `K`.
+pub(crate) fn max_index<K>() -> u32
+where
+    K: Key,
+{
+    const { assert!(0 < K::INDEX_BITS && K::INDEX_BITS <= 32) };
+    u32::MAX >> (32 - K::INDEX_BITS)
+}
+
+/// Returns the largest generation that fits into `K`.
+pub(crate) fn max_generation<K>() -> NonZeroU32
+where
+    K: Key,
+{
+    const { assert!(0 < K::GENERATION_BITS && K::GENERATION_BITS <= 32) };
+    NonZeroU32::new(u32::MAX >> (32 - K::GENERATION_BITS)).unwrap()
+}
+
+/// Returns whether generation `a` is newer than `b` for a slot of `K`.
+///
+/// Generations wrap back to `1` past [`max_generation`], so they are compared on that circle: `a`
+/// is newer if it lies less than half the circle ahead of `b`.
+pub(crate) fn is_newer_generation<K>(a: NonZeroU32, b: NonZeroU32) -> bool
+where
+    K: Key,
+{
+    let circle = max_generation::<K>().get() as u64;
+    let distance = (a.get() as u64 + circle - b.get() as u64) % circle;
+    distance != 0 && distance < circle.div_ceil(2)
+}
+
+impl Key for SlotMapId {
+    type BitsetWord = u64;
+
+    fn slot_map_id(&self) -> SlotMapId {
+        *self
+    }
+}
+
+/// Declares one or more newtype keys implementing [`Key`], along with `Display` and `FromStr`
+/// in the `{index}v{generation}` form of [`SlotMapId`]. With the `serde` feature, keys also
+/// implement `Serialize` and `Deserialize` in the form of the key they wrap.
+///
+/// Keys wrap a [`SlotMapId`] unless another key type is given in parentheses, such as
+/// [`CompactSlotMapId`](crate::CompactSlotMapId) for 4-byte keys.
+///
+/// ```
+/// slotmap::new_key_type! {
+///     /// Identifies a texture.
+///     pub struct TextureKey;
+///     struct MeshKey;
+///     struct ParticleKey(slotmap::CompactSlotMapId);
+/// }
+///
+/// let mut textures = slotmap::SlotMap::<TextureKey, &str>::with_key();
+/// let texture: TextureKey = textures.add("grass");
+/// assert_eq!(textures.get(texture), Some(&"grass"));
+/// assert_eq!(std::mem::size_of::<ParticleKey>(), 4);
+/// ```
+#[macro_export]
+macro_rules! new_key_type {
+    ($(#[$attr:meta])* $vis:vis struct $name:ident; $($rest:tt)*) => {
+        $crate::new_key_type!($(#[$attr])* $vis struct $name($crate::SlotMapId); $($rest)*);
+    };
+    ($(#[$attr:meta])* $vis:vis struct $name:ident($inner:ty); $($rest:tt)*) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[repr(transparent)]
+        $vis struct $name($inner);
+
+        impl ::std::convert::From<$crate::SlotMapId> for $name {
+            fn from(id: $crate::SlotMapId) -> Self {
+                Self(<$inner>::from(id))
+            }
+        }
+
+        impl $crate::Key for $name {
+            const INDEX_BITS: u32 = <$inner as $crate::Key>::INDEX_BITS;
+            const GENERATION_BITS: u32 = <$inner as $crate::Key>::GENERATION_BITS;
+
+            type BitsetWord = <$inner as $crate::Key>::BitsetWord;
+
+            fn slot_map_id(&self) -> $crate::SlotMapId {
+                $crate::Key::slot_map_id(&self.0)
+            }
+        }
+
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                ::std::fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl ::std::str::FromStr for $name {
+            type Err = <$inner as ::std::str::FromStr>::Err;
+
+            fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+                s.parse().map(Self)
+            }
+        }
+
+        $crate::__serialize_key!($name);
+
+        $crate::new_key_type!($($rest)*);
+    };
+    () => {};
+}
+
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __serialize_key {
+    ($name:ident) => {
+        impl $crate::__impl::Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+            where
+                S: $crate::__impl::Serializer,
+            {
+                $crate::__impl::Serialize::serialize(&self.0, serializer)
+            }
+        }
+
+        impl<'de> $crate::__impl::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: $crate::__impl::Deserializer<'de>,
+            {
+                $crate::__impl::Deserialize::deserialize(deserializer).map(Self)
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "serde"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __serialize_key {
+    ($name:ident) => {};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_newer_generation;
+    use crate::{CompactSlotMapId, Key, SecondaryMap, SlotMap, SlotMapId};
+    use std::num::NonZeroU32;
+
+    new_key_type! {
+        struct AppleKey;
+        struct BananaKey;
+    }
+
+    #[test]
+    fn test_typed_keys() {
+        let mut apples = SlotMap::<AppleKey, &str>::with_key();
+        let mut bananas = SlotMap::<BananaKey, &str>::with_key();
+        let apple = apples.add("apple");
+        let banana = bananas.add("banana");
+
+        assert_eq!(apples.get(apple), Some(&"apple"));
+        assert_eq!(bananas.get(banana), Some(&"banana"));
+        assert_eq!(apple.slot_map_id(), banana.slot_map_id());
+
+        let mut weights = SecondaryMap::new();
+        weights.insert(apple, 150);
+        assert_eq!(weights.get(apple), Some(&150));
+
+        assert_eq!(apples.keys().collect::<Vec<_>>(), vec![apple]);
+        assert_eq!(apples.remove(apple), Some("apple"));
+        assert_eq!(apples.get(apple), None);
+    }
+
+    #[test]
+    fn test_typed_key_conversions() {
+        let mut map = SlotMap::<AppleKey, ()>::with_key();
+        let key = map.add(());
+
+        assert_eq!(AppleKey::from_bits(key.to_bits()), Some(key));
+        assert_eq!(key.to_string(), key.slot_map_id().to_string());
+        assert_eq!(key.to_string().parse(), Ok(key));
+        assert_eq!(AppleKey::from(key.slot_map_id()), key);
+        assert_eq!(SlotMapId::from_bits(key.to_bits()), Some(key.slot_map_id()));
+    }
+
+    #[test]
+    fn test_generation_order_wraps() {
+        let generation = |value| NonZeroU32::new(value).unwrap();
+        let newer = |a, b| is_newer_generation::<CompactSlotMapId>(generation(a), generation(b));
+
+        assert!(newer(2, 1));
+        assert!(!newer(1, 2));
+        assert!(!newer(7, 7));
+        assert!(newer(1, u16::MAX as u32));
+        assert!(!newer(u16::MAX as u32, 1));
+        assert!(newer(1 << 14, 1));
+        assert!(!newer(1 << 15 | 1, 1));
+
+        assert!(is_newer_generation::<SlotMapId>(
+            generation(1),
+            generation(u32::MAX)
+        ));
+    }
+}
@@ -1,125 +1,809 @@
-mod bitmap;
+mod chunked_vec;
+mod compact_id;
+mod concurrent;
+mod config;
+mod entry;
+mod free_slot_policy;
+mod free_slots;
+mod generation_policy;
+mod hierarchical_bitset;
 mod id;
+mod iter;
+mod key;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod secondary_map;
+#[cfg(feature = "serde")]
+mod serialization;
+mod snapshot;
+mod sparse_secondary_map;
 
-pub use bitmap::*;
+pub use compact_id::*;
+pub use concurrent::*;
+pub use config::*;
+pub use entry::*;
+pub use free_slot_policy::*;
+pub use generation_policy::*;
+pub use hierarchical_bitset::*;
 pub use id::*;
+pub use iter::*;
+pub use key::*;
+pub use secondary_map::*;
+pub use snapshot::*;
+pub use sparse_secondary_map::*;
 
-use std::{collections::BTreeMap, num::NonZeroU32};
+/// Reached through `$crate` by the serde impls of [`new_key_type!`], so that key declarations do
+/// not need serde as a direct dependency.
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub mod __impl {
+    pub use serde::{Deserialize, Deserializer, Serialize, Serializer};
+}
+
+use free_slots::FreeSlots;
+use key::{max_generation, max_index};
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    num::NonZeroU32,
+    ops::{Index, IndexMut},
+    slice,
+    sync::atomic::{self, AtomicU32},
+};
 
-pub struct SlotMap<T> {
+pub struct SlotMap<K, T>
+where
+    K: Key,
+{
     elements: Vec<T>,
     element_slot_indices: Vec<u32>,
     slot_element_indices: Vec<u32>,
     slot_generations: Vec<NonZeroU32>,
-    freed_bitmaps: BTreeMap<u32, Bitmap>,
+    free_slots: FreeSlots<K::BitsetWord>,
+    generation_policy: GenerationPolicy,
+    retired_slot_count: usize,
+    reserved_slots: HashSet<u32>,
+    pending_reservation_count: AtomicU32,
+    fresh_slot_generation: NonZeroU32,
+    key: PhantomData<fn() -> K>,
 }
 
-impl<T> SlotMap<T> {
+impl<T> SlotMap<SlotMapId, T> {
     pub fn new() -> Self {
+        Self::with_key()
+    }
+
+    pub fn with_generation_policy(generation_policy: GenerationPolicy) -> Self {
+        Self::with_config(SlotMapConfig {
+            generation_policy,
+            ..Default::default()
+        })
+    }
+
+    pub fn with_config(config: SlotMapConfig) -> Self {
+        Self::with_key_and_config(config)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_key_and_capacity(capacity)
+    }
+}
+
+impl<K, T> SlotMap<K, T>
+where
+    K: Key,
+{
+    /// Creates a map handing out keys of type `K`.
+    pub fn with_key() -> Self {
+        Self::with_key_and_config(SlotMapConfig::default())
+    }
+
+    pub fn with_key_and_capacity(capacity: usize) -> Self {
+        let mut map = Self::with_key();
+        map.reserve(capacity);
+        map
+    }
+
+    pub fn with_key_and_config(config: SlotMapConfig) -> Self {
         Self {
             elements: Vec::new(),
             element_slot_indices: Vec::new(),
             slot_element_indices: Vec::new(),
             slot_generations: Vec::new(),
-            freed_bitmaps: BTreeMap::new(),
+            free_slots: FreeSlots::new(config.free_slot_policy),
+            generation_policy: config.generation_policy,
+            retired_slot_count: 0,
+            reserved_slots: HashSet::new(),
+            pending_reservation_count: AtomicU32::new(0),
+            fresh_slot_generation: NonZeroU32::MIN,
+            key: PhantomData,
         }
     }
 
+    pub fn config(&self) -> SlotMapConfig {
+        SlotMapConfig {
+            generation_policy: self.generation_policy,
+            free_slot_policy: self.free_slots.policy(),
+        }
+    }
+
+    pub fn generation_policy(&self) -> GenerationPolicy {
+        self.generation_policy
+    }
+
+    pub fn free_slot_policy(&self) -> FreeSlotPolicy {
+        self.free_slots.policy()
+    }
+
+    /// Returns the number of slots that have been permanently retired under
+    /// [`GenerationPolicy::Retire`], or by bulk removals under [`GenerationPolicy::Panic`].
+    pub fn retired_slot_count(&self) -> usize {
+        self.retired_slot_count
+    }
+
     pub fn len(&self) -> usize {
         self.elements.len()
     }
 
-    pub fn get(&self, id: SlotMapId) -> Option<&T> {
-        let index = id.index() as usize;
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
 
-        if self.slot_generations.get(index) != Some(&id.generation()) {
-            return None;
-        }
+    /// Returns the number of entries the map can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        let element_capacity = self
+            .elements
+            .capacity()
+            .min(self.element_slot_indices.capacity());
+        let slot_capacity = self
+            .slot_generations
+            .capacity()
+            .min(self.slot_element_indices.capacity());
 
-        self.elements.get(self.slot_element_indices[index] as usize)
+        // Free slots are reused before any new slot is needed.
+        let reusable_slot_count =
+            self.free_slots.len() + slot_capacity - self.slot_generations.len();
+        element_capacity.min(self.len() + reusable_slot_count)
     }
 
-    pub fn get_mut(&mut self, id: SlotMapId) -> Option<&mut T> {
-        let index = id.index() as usize;
+    /// Reserves room for at least `additional` more entries.
+    pub fn reserve(&mut self, additional: usize) {
+        self.elements.reserve(additional);
+        self.element_slot_indices.reserve(additional);
 
-        if self.slot_generations.get(index) != Some(&id.generation()) {
-            return None;
+        let additional_slots = additional.saturating_sub(self.free_slots.len());
+        self.slot_generations.reserve(additional_slots);
+        self.slot_element_indices.reserve(additional_slots);
+    }
+
+    /// Shrinks the storage as much as possible. Freed slots at the end of the slot range are
+    /// dropped entirely, and the set of free slots is rebuilt without them.
+    ///
+    /// Slots created afterwards start at the highest generation any dropped slot had reached, so
+    /// keys that referred to the dropped slots stay invalid.
+    pub fn shrink_to_fit(&mut self) {
+        self.flush_reservations();
+
+        let free_slot_indices = self.free_slots.to_vec(&self.slot_element_indices);
+        let mut is_free = vec![false; self.slot_generations.len()];
+        for &slot_index in &free_slot_indices {
+            is_free[slot_index as usize] = true;
         }
 
-        self.elements
-            .get_mut(self.slot_element_indices[index] as usize)
-    }
+        let slot_count = is_free
+            .iter()
+            .rposition(|&is_free| !is_free)
+            .map_or(0, |i| i + 1);
+        if slot_count < self.slot_generations.len() {
+            for &generation in &self.slot_generations[slot_count..] {
+                self.fresh_slot_generation = self.fresh_slot_generation.max(generation);
+            }
 
-    pub fn add(&mut self, item: T) -> SlotMapId {
-        while let Some(mut entry) = self.freed_bitmaps.first_entry() {
-            let bitmap = entry.get_mut();
+            self.slot_generations.truncate(slot_count);
+            self.slot_element_indices.truncate(slot_count);
 
-            if bitmap.is_full() {
-                entry.remove();
-                continue;
+            let mut free_slots = FreeSlots::new(self.free_slots.policy());
+            for &slot_index in free_slot_indices.iter().rev() {
+                if (slot_index as usize) < slot_count {
+                    free_slots.push(slot_index, &mut self.slot_element_indices);
+                }
             }
+            self.free_slots = free_slots;
+        }
 
-            let element_index = self.elements.len();
-            self.elements.push(item);
+        self.elements.shrink_to_fit();
+        self.element_slot_indices.shrink_to_fit();
+        self.slot_generations.shrink_to_fit();
+        self.slot_element_indices.shrink_to_fit();
+        self.reserved_slots.shrink_to_fit();
+    }
 
-            let freed_local_index = bitmap.find_first_empty_index();
-            bitmap.mark_as_filled(freed_local_index);
+    pub fn iter(&self) -> Iter<'_, K, T> {
+        Iter::new(self)
+    }
 
-            let slot_index = bitmap.index() * Bitmap::BITS_PER_WORD + freed_local_index;
-            self.element_slot_indices.push(slot_index);
-            self.slot_element_indices[slot_index as usize] = element_index as u32;
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, T> {
+        IterMut::new(self)
+    }
 
-            let generation = match self.slot_generations[slot_index as usize].checked_add(1) {
-                Some(generation) => generation,
-                None => NonZeroU32::MIN,
-            };
-            self.slot_generations[slot_index as usize] = generation;
+    pub fn keys(&self) -> Keys<'_, K, T> {
+        Keys::new(self)
+    }
+
+    pub fn values(&self) -> slice::Iter<'_, T> {
+        self.elements.iter()
+    }
+
+    pub fn values_mut(&mut self) -> slice::IterMut<'_, T> {
+        self.elements.iter_mut()
+    }
+
+    /// Returns the packed values in dense order, the same order used by iteration.
+    pub fn as_slice(&self) -> &[T] {
+        &self.elements
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.elements
+    }
+
+    /// Returns the position of the entry in [`as_slice`](Self::as_slice).
+    ///
+    /// Dense indices are only stable until the map is next changed structurally: `remove` moves
+    /// the last element into the freed position, `remove_stable` shifts every later element down,
+    /// and sorting reorders everything. Parallel arrays have to mirror those moves.
+    pub fn dense_index_of(&self, key: K) -> Option<usize> {
+        self.element_index_of(key.slot_map_id())
+    }
+
+    /// Returns the key of the entry stored at `dense_index` in [`as_slice`](Self::as_slice).
+    pub fn id_at_dense(&self, dense_index: usize) -> Option<K> {
+        let slot_index = *self.element_slot_indices.get(dense_index)?;
+        let generation = self.slot_generations[slot_index as usize];
+        Some(K::from(SlotMapId::new(slot_index, generation)))
+    }
 
-            return SlotMapId::new(slot_index, generation);
+    /// Removes every entry, yielding them along with their ids.
+    pub fn drain(&mut self) -> Drain<'_, K, T> {
+        Drain::new(self)
+    }
+
+    /// Removes every entry for which `f` returns `false`.
+    pub fn retain(&mut self, mut f: impl FnMut(K, &mut T) -> bool) {
+        let mut element_index = 0;
+
+        while element_index < self.elements.len() {
+            let slot_index = self.element_slot_indices[element_index];
+            let id = SlotMapId::new(slot_index, self.slot_generations[slot_index as usize]);
+
+            if f(K::from(id), &mut self.elements[element_index]) {
+                element_index += 1;
+            } else {
+                // The last element is swapped into `element_index`, so it is visited next.
+                self.swap_remove_element(element_index, id);
+            }
         }
+    }
 
-        let element_index = self.elements.len() as u32;
-        self.elements.push(item);
+    pub fn clear(&mut self) {
+        self.drain();
+    }
 
-        let slot_index = element_index as u32;
-        self.element_slot_indices.push(slot_index);
-        self.slot_element_indices.push(element_index);
+    pub fn contains_key(&self, key: K) -> bool {
+        self.element_index_of(key.slot_map_id()).is_some()
+    }
+
+    pub fn get(&self, key: K) -> Option<&T> {
+        let element_index = self.element_index_of(key.slot_map_id())?;
+        Some(&self.elements[element_index])
+    }
 
-        let generation = NonZeroU32::MIN;
-        self.slot_generations.push(NonZeroU32::MIN);
+    pub fn get_mut(&mut self, key: K) -> Option<&mut T> {
+        let element_index = self.element_index_of(key.slot_map_id())?;
+        Some(&mut self.elements[element_index])
+    }
+
+    /// Returns mutable references to the values of all `keys` at once, or `None` if any key is
+    /// dead or appears more than once.
+    pub fn get_disjoint_mut<const N: usize>(&mut self, keys: [K; N]) -> Option<[&mut T; N]> {
+        let mut element_indices = [0; N];
+
+        for (element_index, key) in element_indices.iter_mut().zip(keys) {
+            *element_index = self.element_index_of(key.slot_map_id())?;
+        }
 
-        SlotMapId::new(slot_index, generation)
+        self.elements.get_disjoint_mut(element_indices).ok()
     }
 
-    pub fn remove(&mut self, id: SlotMapId) -> Option<T> {
+    fn element_index_of(&self, id: SlotMapId) -> Option<usize> {
         let slot_index = id.index() as usize;
+
         if self.slot_generations.get(slot_index) != Some(&id.generation()) {
             return None;
         }
 
+        // A freed slot keeps a dangling element index, so the back-reference decides occupancy.
         let element_index = self.slot_element_indices[slot_index] as usize;
-        if self.elements.len() <= element_index {
+        if self.element_slot_indices.get(element_index) != Some(&id.index()) {
             return None;
         }
 
+        Some(element_index)
+    }
+
+    pub fn add(&mut self, item: T) -> K {
+        self.insert_with_key(|_| item)
+    }
+
+    /// Inserts a value built from its own key, for values that need to know their key up front.
+    pub fn insert_with_key(&mut self, f: impl FnOnce(K) -> T) -> K {
+        let entry = self.vacant_entry();
+        let item = f(entry.id());
+        entry.insert(item)
+    }
+
+    /// Reserves the key of the next insertion without inserting anything yet. Dropping the entry
+    /// leaves the map unchanged.
+    pub fn vacant_entry(&mut self) -> VacantEntry<'_, K, T> {
+        self.flush_reservations();
+
+        let id = match self.free_slots.first() {
+            // The generation was already advanced when the slot was freed.
+            Some(slot_index) => {
+                SlotMapId::new(slot_index, self.slot_generations[slot_index as usize])
+            }
+            None => SlotMapId::new(self.next_slot_index(0), self.fresh_slot_generation),
+        };
+
+        VacantEntry::new(self, id)
+    }
+
+    /// Reserves a key through a shared reference, so that several threads can hand out keys at
+    /// the same time. Nothing is inserted: looking the key up returns `None` until its value is
+    /// provided with [`commit`](Self::commit). Reserved keys always refer to fresh slots, free
+    /// slots are only reused by regular insertions.
+    pub fn reserve_id(&self) -> K {
+        let offset = self
+            .pending_reservation_count
+            .fetch_add(1, atomic::Ordering::Relaxed);
+        let slot_index = self.next_slot_index(offset);
+
+        K::from(SlotMapId::new(slot_index, self.fresh_slot_generation))
+    }
+
+    /// Returns the index of the `offset`-th slot past the current end of the slot range.
+    fn next_slot_index(&self, offset: u32) -> u32 {
+        (self.slot_generations.len() as u64 + offset as u64)
+            .try_into()
+            .ok()
+            .filter(|&slot_index| slot_index <= max_index::<K>())
+            .expect("slot map ran out of slot indices")
+    }
+
+    /// Inserts the value for a key handed out by [`reserve_id`](Self::reserve_id).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is not a reservation that is still waiting for its value.
+    pub fn commit(&mut self, key: K, item: T) {
+        let id = key.slot_map_id();
+        assert!(
+            self.take_reservation(id),
+            "{id} is not a pending reservation"
+        );
+
+        let element_index = self.elements.len() as u32;
+        self.elements.push(item);
+        self.element_slot_indices.push(id.index());
+        self.slot_element_indices[id.index() as usize] = element_index;
+    }
+
+    /// Gives up a key handed out by [`reserve_id`](Self::reserve_id) without providing a value,
+    /// and frees its slot. Returns `false` if `key` is not a pending reservation.
+    pub fn cancel_reservation(&mut self, key: K) -> bool {
+        let id = key.slot_map_id();
+
+        if !self.take_reservation(id) {
+            return false;
+        }
+
+        self.release_slot(id);
+        true
+    }
+
+    fn take_reservation(&mut self, id: SlotMapId) -> bool {
+        self.flush_reservations();
+
+        self.slot_generations.get(id.index() as usize) == Some(&id.generation())
+            && self.reserved_slots.remove(&id.index())
+    }
+
+    /// Materializes the slots handed out by `reserve_id` since the last call.
+    fn flush_reservations(&mut self) {
+        let count = std::mem::take(self.pending_reservation_count.get_mut());
+
+        for _ in 0..count {
+            self.reserved_slots
+                .insert(self.slot_generations.len() as u32);
+            self.slot_generations.push(self.fresh_slot_generation);
+            // Like a freed slot, a reserved slot has an element index that never points back.
+            self.slot_element_indices.push(u32::MAX);
+        }
+    }
+
+    fn occupy_slot(&mut self, id: SlotMapId, item: T) {
+        let element_index = self.elements.len() as u32;
+        let slot_index = id.index();
+        self.elements.push(item);
+        self.element_slot_indices.push(slot_index);
+
+        if (slot_index as usize) < self.slot_generations.len() {
+            let freed_slot_index = self.free_slots.pop_first(&self.slot_element_indices);
+            debug_assert_eq!(freed_slot_index, Some(slot_index));
+
+            self.slot_element_indices[slot_index as usize] = element_index;
+        } else {
+            self.slot_element_indices.push(element_index);
+            self.slot_generations.push(id.generation());
+        }
+    }
+
+    /// Removes the entry by moving the last element into its place, which is `O(1)` but changes
+    /// the iteration order.
+    pub fn remove(&mut self, key: K) -> Option<T> {
+        let id = key.slot_map_id();
+        let element_index = self.element_index_of(id)?;
+        self.check_generation_exhaustion(id);
+        Some(self.swap_remove_element(element_index, id))
+    }
+
+    /// Removes the element of `id` at `element_index` without checking the generation policy.
+    /// Under [`GenerationPolicy::Panic`] an exhausted slot is retired, which keeps bulk removals
+    /// from panicking halfway through.
+    fn swap_remove_element(&mut self, element_index: usize, id: SlotMapId) -> T {
         let removed = self.elements.swap_remove(element_index);
         self.element_slot_indices.swap_remove(element_index);
 
-        let removed_element_index = self.elements.len();
-        if element_index != removed_element_index {
-            let dst_slot_index = self.element_slot_indices[element_index] as usize;
-            self.slot_element_indices.swap(slot_index, dst_slot_index);
+        if element_index != self.elements.len() {
+            let moved_slot_index = self.element_slot_indices[element_index] as usize;
+            self.slot_element_indices[moved_slot_index] = element_index as u32;
         }
 
-        let removed_bitmap_index = id.index() / Bitmap::BITS_PER_WORD;
-        let removed_bitmap_local_index = id.index() % Bitmap::BITS_PER_WORD;
-        self.freed_bitmaps
-            .entry(removed_bitmap_index)
-            .or_insert_with(|| Bitmap::new(removed_bitmap_index))
-            .mark_as_empty(removed_bitmap_local_index);
+        self.release_slot(id);
+        removed
+    }
+
+    /// Removes the entry while keeping the relative order of the remaining entries, shifting
+    /// every later element down by one. This is `O(n)`.
+    pub fn remove_stable(&mut self, key: K) -> Option<T> {
+        let id = key.slot_map_id();
+        let element_index = self.element_index_of(id)?;
+        self.check_generation_exhaustion(id);
+
+        let removed = self.elements.remove(element_index);
+        self.element_slot_indices.remove(element_index);
 
+        for (element_index, &slot_index) in self
+            .element_slot_indices
+            .iter()
+            .enumerate()
+            .skip(element_index)
+        {
+            self.slot_element_indices[slot_index as usize] = element_index as u32;
+        }
+
+        self.release_slot(id);
         Some(removed)
     }
+
+    /// Sorts the dense storage, and with it the iteration order, with a comparator function.
+    /// Every existing key stays valid. The sort is stable.
+    pub fn sort_by(&mut self, mut compare: impl FnMut(&T, &T) -> Ordering) {
+        let mut order = (0..self.elements.len()).collect::<Vec<_>>();
+
+        // Sorting a permutation first leaves the map untouched if `compare` panics.
+        order.sort_by(|&a, &b| compare(&self.elements[a], &self.elements[b]));
+
+        let mut elements = self.elements.drain(..).map(Some).collect::<Vec<_>>();
+        let element_slot_indices = std::mem::take(&mut self.element_slot_indices);
+
+        for (element_index, &previous_element_index) in order.iter().enumerate() {
+            let slot_index = element_slot_indices[previous_element_index];
+            self.elements
+                .extend(elements[previous_element_index].take());
+            self.element_slot_indices.push(slot_index);
+            self.slot_element_indices[slot_index as usize] = element_index as u32;
+        }
+    }
+
+    /// Sorts the dense storage, and with it the iteration order, with a key extraction function.
+    /// Every existing key stays valid. The sort is stable.
+    pub fn sort_by_key<S>(&mut self, mut f: impl FnMut(&T) -> S)
+    where
+        S: Ord,
+    {
+        self.sort_by(|a, b| f(a).cmp(&f(b)));
+    }
+
+    fn check_generation_exhaustion(&self, id: SlotMapId) {
+        if id.generation() == max_generation::<K>()
+            && self.generation_policy == GenerationPolicy::Panic
+        {
+            panic!("generation of slot {} is exhausted", id.index());
+        }
+    }
+
+    /// Frees the slot of `id` after its element has been taken out of the dense storage.
+    fn release_slot(&mut self, id: SlotMapId) {
+        let slot_index = id.index() as usize;
+
+        // Advancing the generation right away invalidates every outstanding copy of `id`.
+        match id
+            .generation()
+            .checked_add(1)
+            .filter(|&generation| generation <= max_generation::<K>())
+        {
+            Some(generation) => self.slot_generations[slot_index] = generation,
+            // `Panic` only gets here through bulk removals, which must not panic.
+            None if self.generation_policy != GenerationPolicy::Wrap => {
+                // The slot keeps its exhausted generation and is never marked as free again.
+                self.retired_slot_count += 1;
+                return;
+            }
+            None => self.slot_generations[slot_index] = NonZeroU32::MIN,
+        }
+
+        self.free_slots
+            .push(id.index(), &mut self.slot_element_indices);
+    }
+
+    /// Checks every internal invariant of the map, panicking with a description of the first
+    /// violation found. Intended for tests and debugging; it walks the whole map.
+    pub fn debug_validate(&self) {
+        if let Err(err) = self.validate() {
+            panic!("{err}");
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.elements.len() != self.element_slot_indices.len() {
+            return Err("element count does not match element slot index count".to_owned());
+        }
+
+        if self.slot_element_indices.len() != self.slot_generations.len() {
+            return Err("slot element index count does not match slot generation count".to_owned());
+        }
+
+        let slot_count = self.slot_generations.len();
+        let mut occupied = vec![false; slot_count];
+
+        if slot_count > 0 && slot_count - 1 > max_index::<K>() as usize {
+            return Err(format!(
+                "{slot_count} slots exceed the index width of the key"
+            ));
+        }
+
+        if let Some(slot_index) = self
+            .slot_generations
+            .iter()
+            .chain([&self.fresh_slot_generation])
+            .position(|&generation| generation > max_generation::<K>())
+        {
+            return Err(format!("generation of slot {slot_index} exceeds the key"));
+        }
+
+        for (element_index, &slot_index) in self.element_slot_indices.iter().enumerate() {
+            let slot_index = slot_index as usize;
+
+            if slot_count <= slot_index {
+                return Err(format!(
+                    "element {element_index} refers to out-of-range slot {slot_index}"
+                ));
+            }
+
+            if occupied[slot_index] {
+                return Err(format!("slot {slot_index} is shared by multiple elements"));
+            }
+
+            if self.slot_element_indices[slot_index] as usize != element_index {
+                return Err(format!(
+                    "slot {slot_index} does not point back to element {element_index}"
+                ));
+            }
+
+            occupied[slot_index] = true;
+        }
+
+        let mut is_free = vec![false; slot_count];
+        let free_slot_indices = self.free_slots.to_vec(&self.slot_element_indices);
+
+        if free_slot_indices.len() != self.free_slots.len() {
+            return Err("free slot count is out of sync".to_owned());
+        }
+
+        for slot_index in free_slot_indices {
+            let slot_index = slot_index as usize;
+
+            if slot_count <= slot_index {
+                return Err(format!("out-of-range slot {slot_index} is marked as free"));
+            }
+
+            if is_free[slot_index] {
+                return Err(format!(
+                    "slot {slot_index} is marked as free more than once"
+                ));
+            }
+
+            is_free[slot_index] = true;
+        }
+
+        if let FreeSlots::LowestIndexFirst(bitset) = &self.free_slots {
+            bitset.debug_validate();
+        }
+
+        for &slot_index in &self.reserved_slots {
+            let slot_index = slot_index as usize;
+
+            if slot_count <= slot_index || occupied[slot_index] || is_free[slot_index] {
+                return Err(format!("slot {slot_index} cannot be reserved"));
+            }
+        }
+
+        let mut retired_slot_count = 0;
+
+        for (slot_index, (&occupied, &is_free)) in occupied.iter().zip(&is_free).enumerate() {
+            if occupied && is_free {
+                return Err(format!("slot {slot_index} is occupied but marked as free"));
+            }
+
+            if !occupied && !is_free && !self.reserved_slots.contains(&(slot_index as u32)) {
+                if self.generation_policy == GenerationPolicy::Wrap {
+                    return Err(format!(
+                        "slot {slot_index} is neither occupied nor marked as free"
+                    ));
+                }
+
+                if self.slot_generations[slot_index] != max_generation::<K>() {
+                    return Err(format!(
+                        "slot {slot_index} is retired before exhausting its generation"
+                    ));
+                }
+
+                retired_slot_count += 1;
+            }
+        }
+
+        if self.retired_slot_count != retired_slot_count {
+            return Err("retired slot count is out of sync".to_owned());
+        }
+
+        Ok(())
+    }
+}
+
+impl<K, T> Default for SlotMap<K, T>
+where
+    K: Key,
+{
+    fn default() -> Self {
+        Self::with_key()
+    }
+}
+
+impl<K, T> Clone for SlotMap<K, T>
+where
+    K: Key,
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            elements: self.elements.clone(),
+            element_slot_indices: self.element_slot_indices.clone(),
+            slot_element_indices: self.slot_element_indices.clone(),
+            slot_generations: self.slot_generations.clone(),
+            free_slots: self.free_slots.clone(),
+            generation_policy: self.generation_policy,
+            retired_slot_count: self.retired_slot_count,
+            reserved_slots: self.reserved_slots.clone(),
+            pending_reservation_count: AtomicU32::new(
+                self.pending_reservation_count
+                    .load(atomic::Ordering::Relaxed),
+            ),
+            fresh_slot_generation: self.fresh_slot_generation,
+            key: PhantomData,
+        }
+    }
+}
+
+impl<K, T> Debug for SlotMap<K, T>
+where
+    K: Key,
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Two maps are equal when they hold equal values under the same keys, regardless of their
+/// iteration order and free slots.
+impl<K, T> PartialEq for SlotMap<K, T>
+where
+    K: Key,
+    T: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K, T> Eq for SlotMap<K, T>
+where
+    K: Key,
+    T: Eq,
+{
+}
+
+impl<K, T> Index<K> for SlotMap<K, T>
+where
+    K: Key,
+{
+    type Output = T;
+
+    fn index(&self, key: K) -> &T {
+        match self.get(key) {
+            Some(value) => value,
+            None => panic!("invalid slot map key {key:?}"),
+        }
+    }
+}
+
+impl<K, T> IndexMut<K> for SlotMap<K, T>
+where
+    K: Key,
+{
+    fn index_mut(&mut self, key: K) -> &mut T {
+        match self.get_mut(key) {
+            Some(value) => value,
+            None => panic!("invalid slot map key {key:?}"),
+        }
+    }
+}
+
+impl<K, T> FromIterator<T> for SlotMap<K, T>
+where
+    K: Key,
+{
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut map = Self::with_key();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, T> Extend<T> for SlotMap<K, T>
+where
+    K: Key,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+
+        for item in iter {
+            self.add(item);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -141,6 +825,482 @@ mod tests {
         assert_eq!(map.get(id), None);
     }
 
+    #[test]
+    fn test_slotmap_insert_with_key() {
+        let mut map = SlotMap::new();
+        let id1 = map.insert_with_key(|id| id);
+        let id2 = map.insert_with_key(|id| id);
+        assert_eq!(map.get(id1), Some(&id1));
+        assert_eq!(map.get(id2), Some(&id2));
+
+        map.remove(id1);
+        let id3 = map.insert_with_key(|id| id);
+        assert_eq!(map.get(id3), Some(&id3));
+    }
+
+    #[test]
+    fn test_slotmap_iter() {
+        let mut map = SlotMap::new();
+        let id1 = map.add(1);
+        let id2 = map.add(2);
+        let id3 = map.add(3);
+        map.remove(id2);
+
+        let mut entries = map.iter().map(|(id, &v)| (id, v)).collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(entries, vec![(id1, 1), (id3, 3)]);
+
+        for (_, v) in map.iter_mut() {
+            *v *= 10;
+        }
+        let mut values = map.values().copied().collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![10, 30]);
+
+        let mut keys = map.keys().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, vec![id1, id3]);
+
+        let mut owned = map.into_iter().collect::<Vec<_>>();
+        owned.sort();
+        assert_eq!(owned, vec![(id1, 10), (id3, 30)]);
+    }
+
+    #[test]
+    fn test_slotmap_drain() {
+        let mut map = SlotMap::new();
+        let id1 = map.add(1);
+        let id2 = map.add(2);
+
+        let mut drained = map.drain().collect::<Vec<_>>();
+        drained.sort();
+        assert_eq!(drained, vec![(id1, 1), (id2, 2)]);
+        assert!(map.is_empty());
+        assert_eq!(map.get(id1), None);
+        assert_eq!(map.get(id2), None);
+
+        let id3 = map.add(3);
+        assert_eq!(map.get(id3), Some(&3));
+    }
+
+    #[test]
+    fn test_slotmap_retain_and_clear() {
+        let mut map = SlotMap::new();
+        let ids = (0..10).map(|v| map.add(v)).collect::<Vec<_>>();
+
+        map.retain(|_, v| *v % 2 == 0);
+        assert_eq!(map.len(), 5);
+        for (v, id) in ids.iter().enumerate() {
+            assert_eq!(map.get(*id).is_some(), v % 2 == 0);
+        }
+
+        map.clear();
+        assert!(map.is_empty());
+        assert!(ids.iter().all(|id| map.get(*id).is_none()));
+    }
+
+    #[test]
+    fn test_slotmap_stale_id_after_reuse() {
+        let mut map = SlotMap::new();
+        let id1 = map.add(1);
+        let id2 = map.add(2);
+        let id3 = map.add(3);
+
+        assert_eq!(map.remove(id1), Some(1));
+        assert_eq!(map.remove(id2), Some(2));
+        let id4 = map.add(4);
+
+        assert_eq!(map.get(id1), None);
+        assert_eq!(map.get(id2), None);
+        assert_eq!(map.get(id3), Some(&3));
+        assert_eq!(map.get(id4), Some(&4));
+        map.debug_validate();
+    }
+
+    #[test]
+    fn test_slotmap_double_remove() {
+        let mut map = SlotMap::new();
+        let id1 = map.add(1);
+        let id2 = map.add(2);
+
+        assert_eq!(map.remove(id1), Some(1));
+        assert_eq!(map.remove(id1), None);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(id2), Some(&2));
+        map.debug_validate();
+
+        let id3 = map.add(3);
+        assert_ne!(id1, id3);
+        assert_eq!(map.remove(id1), None);
+        assert_eq!(map.get(id3), Some(&3));
+        map.debug_validate();
+    }
+
+    #[test]
+    fn test_slotmap_vacant_entry() {
+        let mut map = SlotMap::new();
+        let id1 = map.add(1);
+        map.remove(id1);
+
+        let id2 = map.vacant_entry().id();
+        assert!(map.is_empty());
+        map.debug_validate();
+
+        let entry = map.vacant_entry();
+        assert_eq!(entry.id(), id2);
+        assert_eq!(entry.insert(2), id2);
+        assert_eq!(map.get(id2), Some(&2));
+        assert_eq!(map.get(id1), None);
+
+        let entry = map.vacant_entry();
+        let id3 = entry.id();
+        assert_eq!(entry.insert(3), id3);
+        assert_eq!(map.get(id3), Some(&3));
+        map.debug_validate();
+    }
+
+    #[test]
+    fn test_slotmap_get_disjoint_mut() {
+        let mut map = SlotMap::new();
+        let id1 = map.add(1);
+        let id2 = map.add(2);
+        let id3 = map.add(3);
+
+        let [a, b] = map.get_disjoint_mut([id1, id3]).unwrap();
+        std::mem::swap(a, b);
+        assert_eq!(map.get(id1), Some(&3));
+        assert_eq!(map.get(id3), Some(&1));
+
+        assert!(map.get_disjoint_mut([id1, id1]).is_none());
+
+        map.remove(id2);
+        assert!(map.get_disjoint_mut([id1, id2]).is_none());
+        assert!(map.get_disjoint_mut::<0>([]).is_some());
+    }
+
+    #[test]
+    fn test_slotmap_free_slot_policy_lifo() {
+        let mut map = SlotMap::with_config(SlotMapConfig {
+            free_slot_policy: FreeSlotPolicy::Lifo,
+            ..Default::default()
+        });
+        let ids = (0..4).map(|v| map.add(v)).collect::<Vec<_>>();
+
+        map.remove(ids[1]);
+        map.remove(ids[3]);
+        map.remove(ids[0]);
+        assert_eq!(map.add(4).index(), ids[0].index());
+        assert_eq!(map.add(5).index(), ids[3].index());
+        assert_eq!(map.add(6).index(), ids[1].index());
+        assert_eq!(map.add(7).index(), 4);
+        map.debug_validate();
+    }
+
+    #[test]
+    fn test_slotmap_free_slot_policy_lowest_index_first() {
+        let mut map = SlotMap::with_config(SlotMapConfig {
+            free_slot_policy: FreeSlotPolicy::LowestIndexFirst,
+            ..Default::default()
+        });
+        let ids = (0..200).map(|v| map.add(v)).collect::<Vec<_>>();
+
+        map.remove(ids[130]);
+        map.remove(ids[3]);
+        map.remove(ids[70]);
+        assert_eq!(map.add(200).index(), 3);
+        assert_eq!(map.add(201).index(), 70);
+        assert_eq!(map.add(202).index(), 130);
+        assert_eq!(map.add(203).index(), 200);
+        map.debug_validate();
+    }
+
+    #[test]
+    fn test_slotmap_remove_stable() {
+        let mut map = SlotMap::new();
+        let ids = (0..6).map(|v| map.add(v)).collect::<Vec<_>>();
+
+        assert_eq!(map.remove_stable(ids[1]), Some(1));
+        assert_eq!(map.remove_stable(ids[4]), Some(4));
+        assert_eq!(map.remove_stable(ids[4]), None);
+        assert_eq!(map.values().copied().collect::<Vec<_>>(), vec![0, 2, 3, 5]);
+
+        let id6 = map.add(6);
+        assert_eq!(
+            map.iter().map(|(id, &v)| (id, v)).collect::<Vec<_>>(),
+            vec![(ids[0], 0), (ids[2], 2), (ids[3], 3), (ids[5], 5), (id6, 6)]
+        );
+        map.debug_validate();
+    }
+
+    #[test]
+    fn test_slotmap_sort() {
+        let mut map = SlotMap::new();
+        let ids = [5, 3, 8, 1, 3].map(|v| map.add(v));
+        map.remove(ids[2]);
+
+        map.sort_by_key(|&v| v);
+        assert_eq!(map.values().copied().collect::<Vec<_>>(), vec![1, 3, 3, 5]);
+        assert_eq!(
+            map.keys().collect::<Vec<_>>(),
+            vec![ids[3], ids[1], ids[4], ids[0]]
+        );
+        for (id, v) in ids.iter().zip([5, 3, 8, 1, 3]) {
+            if *id != ids[2] {
+                assert_eq!(map.get(*id), Some(&v));
+            }
+        }
+        map.debug_validate();
+
+        map.sort_by(|a, b| b.cmp(a));
+        assert_eq!(map.values().copied().collect::<Vec<_>>(), vec![5, 3, 3, 1]);
+        map.debug_validate();
+    }
+
+    #[test]
+    fn test_slotmap_dense_indices() {
+        let mut map = SlotMap::new();
+        let ids = [10, 20, 30].map(|v| map.add(v));
+
+        for (dense_index, id) in ids.iter().enumerate() {
+            assert_eq!(map.dense_index_of(*id), Some(dense_index));
+            assert_eq!(map.id_at_dense(dense_index), Some(*id));
+        }
+        assert_eq!(map.id_at_dense(3), None);
+
+        map.remove(ids[0]);
+        assert_eq!(map.dense_index_of(ids[0]), None);
+        assert_eq!(map.dense_index_of(ids[2]), Some(0));
+        assert_eq!(map.as_slice(), &[30, 20]);
+
+        map.as_mut_slice()[1] += 1;
+        assert_eq!(map.get(ids[1]), Some(&21));
+        assert_eq!(map.id_at_dense(1), Some(ids[1]));
+    }
+
+    #[test]
+    fn test_slotmap_reserve_and_commit() {
+        let mut map = SlotMap::new();
+        let id0 = map.add(0);
+        map.remove(id0);
+
+        let reserved = [map.reserve_id(), map.reserve_id(), map.reserve_id()];
+        assert_eq!(reserved.map(|id| id.index()), [1, 2, 3]);
+        assert_eq!(map.get(reserved[0]), None);
+        assert_eq!(map.remove(reserved[0]), None);
+
+        // Regular insertions neither reuse nor collide with reserved slots.
+        let id4 = map.add(4);
+        assert_eq!(id4.index(), 0);
+        let id5 = map.add(5);
+        assert_eq!(id5.index(), 4);
+        map.debug_validate();
+
+        map.commit(reserved[2], 3);
+        map.commit(reserved[0], 1);
+        assert!(map.cancel_reservation(reserved[1]));
+        assert!(!map.cancel_reservation(reserved[1]));
+        assert!(!map.cancel_reservation(reserved[0]));
+        map.debug_validate();
+
+        assert_eq!(map.get(reserved[0]), Some(&1));
+        assert_eq!(map.get(reserved[1]), None);
+        assert_eq!(map.get(reserved[2]), Some(&3));
+        assert_eq!(map.len(), 4);
+
+        let reused = map.add(2);
+        assert_eq!(reused.index(), reserved[1].index());
+        assert_ne!(reused, reserved[1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a pending reservation")]
+    fn test_slotmap_commit_twice() {
+        let mut map = SlotMap::new();
+        let id = map.reserve_id();
+        map.commit(id, 0);
+        map.commit(id, 1);
+    }
+
+    #[test]
+    fn test_slotmap_reserve_from_threads() {
+        let mut map = SlotMap::new();
+        let ids = std::thread::scope(|scope| {
+            let handles = (0..4)
+                .map(|_| scope.spawn(|| (0..100).map(|_| map.reserve_id()).collect::<Vec<_>>()))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 400);
+        for (v, id) in ids.iter().enumerate() {
+            map.commit(*id, v);
+        }
+        for (v, id) in ids.iter().enumerate() {
+            assert_eq!(map.get(*id), Some(&v));
+        }
+        map.debug_validate();
+    }
+
+    #[test]
+    fn test_slotmap_std_traits() {
+        let mut map = (0..4).collect::<SlotMap<SlotMapId, _>>();
+        let ids = map.keys().collect::<Vec<_>>();
+        assert_eq!(map[ids[2]], 2);
+        map[ids[2]] = 20;
+        assert!(map.contains_key(ids[2]));
+
+        let mut clone = map.clone();
+        assert_eq!(clone, map);
+        clone.remove(ids[0]);
+        assert_ne!(clone, map);
+        clone.extend([0]);
+        assert_ne!(clone, map);
+        clone.debug_validate();
+
+        // Equality does not depend on the iteration order.
+        let mut sorted = map.clone();
+        sorted.sort_by(|a, b| b.cmp(a));
+        assert_eq!(sorted, map);
+
+        map.remove(ids[1]);
+        assert!(!map.contains_key(ids[1]));
+        assert_eq!(
+            format!("{map:?}"),
+            format!("{{{:?}: 0, {:?}: 3, {:?}: 20}}", ids[0], ids[3], ids[2])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid slot map key")]
+    fn test_slotmap_index_stale_key() {
+        let mut map = SlotMap::new();
+        let id = map.add(0);
+        map.remove(id);
+        let _ = map[id];
+    }
+
+    #[test]
+    fn test_slotmap_capacity() {
+        let mut map = SlotMap::with_capacity(10);
+        assert!(map.capacity() >= 10);
+
+        let ids = (0..10).map(|v| map.add(v)).collect::<Vec<_>>();
+        for id in &ids[5..] {
+            map.remove(*id);
+        }
+        map.reserve(20);
+        assert!(map.capacity() >= 25);
+        map.debug_validate();
+    }
+
+    #[test]
+    fn test_slotmap_shrink_to_fit() {
+        for free_slot_policy in [FreeSlotPolicy::Lifo, FreeSlotPolicy::LowestIndexFirst] {
+            let mut map = SlotMap::with_config(SlotMapConfig {
+                free_slot_policy,
+                ..Default::default()
+            });
+            let ids = (0..100).map(|v| map.add(v)).collect::<Vec<_>>();
+            for id in ids.iter().skip(2).filter(|id| id.index() != 50) {
+                map.remove(*id);
+            }
+            map.shrink_to_fit();
+            map.debug_validate();
+
+            assert_eq!(map.slot_generations.len(), 51);
+            for id in [ids[0], ids[1], ids[50]] {
+                assert_eq!(map.get(id), Some(&(id.index() as i32)));
+            }
+
+            // New slots past the trimmed range must not revive keys of the dropped slots.
+            let new_ids = (0..100).map(|v| map.add(v)).collect::<Vec<_>>();
+            for id in &ids[51..] {
+                assert_eq!(map.get(*id), None);
+                assert!(!new_ids.contains(id));
+            }
+            map.debug_validate();
+        }
+    }
+
+    fn exhaust_generation<T>(map: &mut SlotMap<SlotMapId, T>, item: T) -> SlotMapId {
+        let id = map.add(item);
+        map.slot_generations[id.index() as usize] = NonZeroU32::MAX;
+        SlotMapId::new(id.index(), NonZeroU32::MAX)
+    }
+
+    #[test]
+    fn test_slotmap_generation_policy_wrap() {
+        let mut map = SlotMap::with_generation_policy(GenerationPolicy::Wrap);
+        let id1 = exhaust_generation(&mut map, 1);
+
+        assert_eq!(map.remove(id1), Some(1));
+        let id2 = map.add(2);
+        assert_eq!(id2.index(), id1.index());
+        assert_eq!(id2.generation(), NonZeroU32::MIN);
+        assert_eq!(map.retired_slot_count(), 0);
+        map.debug_validate();
+    }
+
+    #[test]
+    fn test_slotmap_generation_policy_retire() {
+        let mut map = SlotMap::with_generation_policy(GenerationPolicy::Retire);
+        let id1 = exhaust_generation(&mut map, 1);
+
+        assert_eq!(map.remove(id1), Some(1));
+        assert_eq!(map.remove(id1), None);
+        assert_eq!(map.get(id1), None);
+        assert_eq!(map.retired_slot_count(), 1);
+
+        let id2 = map.add(2);
+        assert_ne!(id2.index(), id1.index());
+        assert_eq!(map.get(id1), None);
+        map.debug_validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "generation of slot 0 is exhausted")]
+    fn test_slotmap_generation_policy_panic() {
+        let mut map = SlotMap::with_generation_policy(GenerationPolicy::Panic);
+        let id = exhaust_generation(&mut map, 1);
+        map.remove(id);
+    }
+
+    #[test]
+    fn test_slotmap_generation_policy_panic_bulk_removal() {
+        let mut map = SlotMap::with_generation_policy(GenerationPolicy::Panic);
+        map.add(0);
+        let exhausted = exhaust_generation(&mut map, 1);
+        map.add(2);
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get(exhausted), None);
+        assert_eq!(map.retired_slot_count(), 1);
+        map.debug_validate();
+
+        let exhausted = exhaust_generation(&mut map, 3);
+        map.add(4);
+        map.add(5);
+
+        // Dropping a partially consumed drain removes the exhausted entry without panicking.
+        assert_eq!(map.drain().next().map(|(_, item)| item), Some(5));
+        assert!(map.is_empty());
+        assert_eq!(map.get(exhausted), None);
+        assert_eq!(map.retired_slot_count(), 2);
+        map.debug_validate();
+
+        let exhausted = exhaust_generation(&mut map, 6);
+        map.add(7);
+        map.retain(|_, &mut item| item == 7);
+        assert_eq!(map.values().collect::<Vec<_>>(), vec![&7]);
+        assert_eq!(map.get(exhausted), None);
+        assert_eq!(map.retired_slot_count(), 3);
+        map.debug_validate();
+    }
+
     #[test]
     fn test_slotmap_complex_1() {
         let mut map = SlotMap::new();
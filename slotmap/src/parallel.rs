@@ -0,0 +1,72 @@
+use crate::{Key, SlotMap, SlotMapId};
+use rayon::prelude::*;
+
+impl<K, T> SlotMap<K, T>
+where
+    K: Key + Send,
+{
+    /// Iterates over the entries in parallel. The work is split over the dense storage, so every
+    /// thread gets a contiguous run of values.
+    pub fn par_iter(&self) -> impl IndexedParallelIterator<Item = (K, &T)>
+    where
+        T: Sync,
+    {
+        let slot_generations = &self.slot_generations;
+
+        self.element_slot_indices
+            .par_iter()
+            .zip(self.elements.par_iter())
+            .map(move |(&slot_index, element)| {
+                let id = SlotMapId::new(slot_index, slot_generations[slot_index as usize]);
+                (K::from(id), element)
+            })
+    }
+
+    pub fn par_iter_mut(&mut self) -> impl IndexedParallelIterator<Item = (K, &mut T)>
+    where
+        T: Send,
+    {
+        let slot_generations = &self.slot_generations;
+
+        self.element_slot_indices
+            .par_iter()
+            .zip(self.elements.par_iter_mut())
+            .map(move |(&slot_index, element)| {
+                let id = SlotMapId::new(slot_index, slot_generations[slot_index as usize]);
+                (K::from(id), element)
+            })
+    }
+
+    pub fn par_values_mut(&mut self) -> rayon::slice::IterMut<'_, T>
+    where
+        T: Send,
+    {
+        self.elements.par_iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SlotMap, SlotMapId};
+    use rayon::prelude::*;
+
+    #[test]
+    fn test_par_iter() {
+        let mut map = (0..10_000u64).collect::<SlotMap<SlotMapId, _>>();
+        let removed = map.keys().step_by(3).collect::<Vec<_>>();
+        for id in removed {
+            map.remove(id);
+        }
+
+        let expected = map.iter().map(|(id, &v)| (id, v)).collect::<Vec<_>>();
+        let actual = map.par_iter().map(|(id, &v)| (id, v)).collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+
+        map.par_iter_mut()
+            .for_each(|(id, v)| *v += id.index() as u64);
+        map.par_values_mut().for_each(|v| *v *= 2);
+        for (id, v) in expected {
+            assert_eq!(map[id], (v + id.index() as u64) * 2);
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use crate::{key::is_newer_generation, Key, SlotMap, SlotMapId};
+use std::{marker::PhantomData, num::NonZeroU32};
+
+struct SecondaryEntry<V> {
+    generation: NonZeroU32,
+    value: V,
+}
+
+/// Associates side data with the ids of a primary [`SlotMap`], storing it densely by slot index.
+///
+/// Every entry remembers the generation it was inserted with, so ids of removed or reused slots
+/// never observe another id's data. Inserting with a newer id replaces the entry of the same slot,
+/// while inserting with an older one is ignored. Generations are compared on the circle they wrap
+/// around under [`GenerationPolicy::Wrap`](crate::GenerationPolicy::Wrap).
+pub struct SecondaryMap<K, V> {
+    entries: Vec<Option<SecondaryEntry<V>>>,
+    len: usize,
+    key: PhantomData<fn() -> K>,
+}
+
+impl<K, V> SecondaryMap<K, V>
+where
+    K: Key,
+{
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            len: 0,
+            key: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains_key(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn get(&self, key: K) -> Option<&V> {
+        let id = key.slot_map_id();
+
+        match self.entries.get(id.index() as usize) {
+            Some(Some(entry)) if entry.generation == id.generation() => Some(&entry.value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: K) -> Option<&mut V> {
+        let id = key.slot_map_id();
+
+        match self.entries.get_mut(id.index() as usize) {
+            Some(Some(entry)) if entry.generation == id.generation() => Some(&mut entry.value),
+            _ => None,
+        }
+    }
+
+    /// Inserts a value for `id`, returning the previous value stored for the very same id.
+    ///
+    /// The value is dropped if the slot holds an entry of a newer generation than `id`.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let id = key.slot_map_id();
+
+        let index = id.index() as usize;
+
+        if self.entries.len() <= index {
+            self.entries.resize_with(index + 1, || None);
+        }
+
+        let slot = &mut self.entries[index];
+        match slot {
+            Some(entry) if entry.generation == id.generation() => {
+                Some(std::mem::replace(&mut entry.value, value))
+            }
+            Some(entry) if is_newer_generation::<K>(id.generation(), entry.generation) => {
+                *entry = SecondaryEntry {
+                    generation: id.generation(),
+                    value,
+                };
+                None
+            }
+            Some(_) => None,
+            None => {
+                *slot = Some(SecondaryEntry {
+                    generation: id.generation(),
+                    value,
+                });
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: K) -> Option<V> {
+        let id = key.slot_map_id();
+
+        let slot = self.entries.get_mut(id.index() as usize)?;
+
+        match slot {
+            Some(entry) if entry.generation == id.generation() => {
+                self.len -= 1;
+                slot.take().map(|entry| entry.value)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (K, &V)> {
+        self.entries.iter().enumerate().filter_map(|(index, slot)| {
+            slot.as_ref().map(|entry| {
+                (
+                    K::from(SlotMapId::new(index as u32, entry.generation)),
+                    &entry.value,
+                )
+            })
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (K, &mut V)> {
+        self.entries
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                slot.as_mut().map(|entry| {
+                    (
+                        K::from(SlotMapId::new(index as u32, entry.generation)),
+                        &mut entry.value,
+                    )
+                })
+            })
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(K, &mut V) -> bool) {
+        for (index, slot) in self.entries.iter_mut().enumerate() {
+            if let Some(entry) = slot {
+                if !f(
+                    K::from(SlotMapId::new(index as u32, entry.generation)),
+                    &mut entry.value,
+                ) {
+                    *slot = None;
+                    self.len -= 1;
+                }
+            }
+        }
+    }
+
+    /// Drops every entry whose id is no longer alive in `primary`.
+    pub fn retain_alive<T>(&mut self, primary: &SlotMap<K, T>) {
+        self.retain(|id, _| primary.get(id).is_some());
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.len = 0;
+    }
+}
+
+impl<K, V> Default for SecondaryMap<K, V>
+where
+    K: Key,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CompactSlotMapId;
+
+    #[test]
+    fn test_secondary_map_insert_get() {
+        let mut primary = SlotMap::new();
+        let id1 = primary.add("a");
+        let id2 = primary.add("b");
+
+        let mut secondary = SecondaryMap::new();
+        assert_eq!(secondary.insert(id1, 1), None);
+        assert_eq!(secondary.insert(id2, 2), None);
+        assert_eq!(secondary.insert(id2, 3), Some(2));
+
+        assert_eq!(secondary.len(), 2);
+        assert_eq!(secondary.get(id1), Some(&1));
+        assert_eq!(secondary.get(id2), Some(&3));
+    }
+
+    #[test]
+    fn test_secondary_map_stale_entries() {
+        let mut primary = SlotMap::new();
+        let id1 = primary.add("a");
+
+        let mut secondary = SecondaryMap::new();
+        secondary.insert(id1, 1);
+
+        primary.remove(id1);
+        let id2 = primary.add("b");
+        assert_eq!(id1.index(), id2.index());
+        assert_eq!(secondary.get(id2), None);
+
+        assert_eq!(secondary.insert(id2, 2), None);
+        assert_eq!(secondary.len(), 1);
+        assert_eq!(secondary.get(id1), None);
+        assert_eq!(secondary.get(id2), Some(&2));
+
+        // Inserting through the stale id leaves the entry of the slot's new occupant alone.
+        assert_eq!(secondary.insert(id1, 3), None);
+        assert_eq!(secondary.get(id1), None);
+        assert_eq!(secondary.get(id2), Some(&2));
+        assert_eq!(secondary.remove(id1), None);
+        assert_eq!(secondary.remove(id2), Some(2));
+        assert!(secondary.is_empty());
+    }
+
+    #[test]
+    fn test_secondary_map_generation_wrap() {
+        let mut primary = SlotMap::<CompactSlotMapId, u32>::with_key();
+        let mut secondary = SecondaryMap::new();
+
+        let mut id = primary.add(0);
+        while id.generation().get() < u16::MAX {
+            primary.remove(id);
+            id = primary.add(0);
+        }
+        secondary.insert(id, "old");
+
+        primary.remove(id);
+        let wrapped = primary.add(0);
+        assert_eq!(wrapped.index(), id.index());
+        assert_eq!(wrapped.generation().get(), 1);
+
+        assert_eq!(secondary.insert(wrapped, "new"), None);
+        assert_eq!(secondary.get(wrapped), Some(&"new"));
+        assert_eq!(secondary.get(id), None);
+        assert_eq!(secondary.len(), 1);
+
+        assert_eq!(secondary.insert(id, "stale"), None);
+        assert_eq!(secondary.get(wrapped), Some(&"new"));
+        assert_eq!(secondary.get(id), None);
+    }
+
+    #[test]
+    fn test_secondary_map_retain_alive() {
+        let mut primary = SlotMap::new();
+        let id1 = primary.add("a");
+        let id2 = primary.add("b");
+
+        let mut secondary = SecondaryMap::new();
+        secondary.insert(id1, 1);
+        secondary.insert(id2, 2);
+
+        primary.remove(id1);
+        secondary.retain_alive(&primary);
+        assert_eq!(secondary.iter().collect::<Vec<_>>(), vec![(id2, &2)]);
+    }
+}
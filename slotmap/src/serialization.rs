@@ -0,0 +1,302 @@
+use crate::{free_slots::FreeSlots, Key, SlotMap, SlotMapConfig};
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    marker::PhantomData,
+    num::NonZeroU32,
+    sync::atomic::{self, AtomicU32},
+};
+
+/// The persisted form of a [`SlotMap`]. Besides the values it keeps every slot generation and
+/// the free slots in reuse order, so that ids stay valid across a save and load, and so that the
+/// loaded map hands out the same ids the saved one would have.
+#[derive(Serialize)]
+struct SlotMapRef<'a, T> {
+    config: SlotMapConfig,
+    slot_generations: Cow<'a, [NonZeroU32]>,
+    free_slot_indices: Vec<u32>,
+    reserved_slot_indices: Vec<u32>,
+    fresh_slot_generation: NonZeroU32,
+    element_slot_indices: &'a [u32],
+    elements: &'a [T],
+}
+
+#[derive(Deserialize)]
+struct SlotMapData<T> {
+    config: SlotMapConfig,
+    slot_generations: Vec<NonZeroU32>,
+    free_slot_indices: Vec<u32>,
+    #[serde(default)]
+    reserved_slot_indices: Vec<u32>,
+    #[serde(default = "min_generation")]
+    fresh_slot_generation: NonZeroU32,
+    element_slot_indices: Vec<u32>,
+    elements: Vec<T>,
+}
+
+fn min_generation() -> NonZeroU32 {
+    NonZeroU32::MIN
+}
+
+impl<K, T> Serialize for SlotMap<K, T>
+where
+    K: Key,
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut slot_generations = Cow::Borrowed(self.slot_generations.as_slice());
+        let mut reserved_slot_indices = self.reserved_slots.iter().copied().collect::<Vec<_>>();
+
+        // Reservations made since the last change only exist as a count, so their fresh slots are
+        // written out as if they had been materialized.
+        let pending_reservation_count = self
+            .pending_reservation_count
+            .load(atomic::Ordering::Relaxed);
+        for _ in 0..pending_reservation_count {
+            reserved_slot_indices.push(slot_generations.len() as u32);
+            slot_generations.to_mut().push(self.fresh_slot_generation);
+        }
+        reserved_slot_indices.sort_unstable();
+
+        SlotMapRef {
+            config: self.config(),
+            slot_generations,
+            free_slot_indices: self.free_slots.to_vec(&self.slot_element_indices),
+            reserved_slot_indices,
+            fresh_slot_generation: self.fresh_slot_generation,
+            element_slot_indices: &self.element_slot_indices,
+            elements: &self.elements,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, K, T> Deserialize<'de> for SlotMap<K, T>
+where
+    K: Key,
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let data = SlotMapData::deserialize(deserializer)?;
+        SlotMap::from_data(data).map_err(D::Error::custom)
+    }
+}
+
+impl<K, T> SlotMap<K, T>
+where
+    K: Key,
+{
+    fn from_data(data: SlotMapData<T>) -> Result<Self, String> {
+        let slot_count = data.slot_generations.len();
+
+        if data.elements.len() != data.element_slot_indices.len() {
+            return Err("element count does not match element slot index count".to_owned());
+        }
+
+        let mut occupied = vec![false; slot_count];
+        let mut slot_element_indices = vec![0; slot_count];
+
+        for (element_index, &slot_index) in data.element_slot_indices.iter().enumerate() {
+            match occupied.get_mut(slot_index as usize) {
+                Some(occupied) if !*occupied => *occupied = true,
+                _ => {
+                    return Err(format!(
+                        "slot {slot_index} cannot hold element {element_index}"
+                    ))
+                }
+            }
+
+            slot_element_indices[slot_index as usize] = element_index as u32;
+        }
+
+        let mut free_slots = FreeSlots::new(data.config.free_slot_policy);
+
+        // The free list is a stack, so pushing in reverse restores the saved reuse order.
+        for &slot_index in data.free_slot_indices.iter().rev() {
+            match occupied.get_mut(slot_index as usize) {
+                Some(occupied) if !*occupied => *occupied = true,
+                _ => return Err(format!("slot {slot_index} cannot be marked as free")),
+            }
+
+            free_slots.push(slot_index, &mut slot_element_indices);
+        }
+
+        for &slot_index in &data.reserved_slot_indices {
+            match occupied.get_mut(slot_index as usize) {
+                Some(occupied) if !*occupied => *occupied = true,
+                _ => return Err(format!("slot {slot_index} cannot be reserved")),
+            }
+
+            slot_element_indices[slot_index as usize] = u32::MAX;
+        }
+
+        let map = Self {
+            retired_slot_count: slot_count
+                - data.elements.len()
+                - data.free_slot_indices.len()
+                - data.reserved_slot_indices.len(),
+            elements: data.elements,
+            element_slot_indices: data.element_slot_indices,
+            slot_element_indices,
+            slot_generations: data.slot_generations,
+            free_slots,
+            generation_policy: data.config.generation_policy,
+            reserved_slots: HashSet::from_iter(data.reserved_slot_indices),
+            pending_reservation_count: AtomicU32::new(0),
+            fresh_slot_generation: data.fresh_slot_generation,
+            key: PhantomData,
+        };
+        map.validate()?;
+
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FreeSlotPolicy, GenerationPolicy, Key, SlotMap, SlotMapConfig, SlotMapId};
+
+    fn round_trip(map: &SlotMap<SlotMapId, String>) -> SlotMap<SlotMapId, String> {
+        let json = serde_json::to_string(map).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn churned_map(
+        config: SlotMapConfig,
+    ) -> (SlotMap<SlotMapId, String>, Vec<SlotMapId>, Vec<SlotMapId>) {
+        let mut map = SlotMap::with_config(config);
+        let ids = (0..100).map(|v| map.add(v.to_string())).collect::<Vec<_>>();
+        let mut live_ids = vec![];
+        let mut dead_ids = vec![];
+
+        for (v, id) in ids.into_iter().enumerate() {
+            if v % 3 == 0 {
+                map.remove(id);
+                dead_ids.push(id);
+            } else {
+                live_ids.push(id);
+            }
+        }
+
+        for v in 0..10 {
+            live_ids.push(map.add(format!("re-{v}")));
+        }
+
+        (map, live_ids, dead_ids)
+    }
+
+    #[test]
+    fn test_serde_preserves_ids() {
+        for free_slot_policy in [FreeSlotPolicy::Lifo, FreeSlotPolicy::LowestIndexFirst] {
+            let config = SlotMapConfig {
+                free_slot_policy,
+                ..Default::default()
+            };
+            let (mut map, live_ids, dead_ids) = churned_map(config);
+            let mut loaded = round_trip(&map);
+            loaded.debug_validate();
+
+            assert_eq!(loaded.config(), config);
+            assert_eq!(loaded.len(), map.len());
+            for id in &live_ids {
+                assert_eq!(loaded.get(*id), map.get(*id));
+            }
+            for id in &dead_ids {
+                assert_eq!(loaded.get(*id), None);
+            }
+
+            for v in 0..50 {
+                assert_eq!(loaded.add(v.to_string()), map.add(v.to_string()));
+            }
+            loaded.debug_validate();
+        }
+    }
+
+    #[test]
+    fn test_serde_preserves_retired_slots() {
+        let mut map = SlotMap::with_generation_policy(GenerationPolicy::Retire);
+        let id = map.add("a".to_owned());
+        map.slot_generations[id.index() as usize] = std::num::NonZeroU32::MAX;
+        map.remove(SlotMapId::new(id.index(), std::num::NonZeroU32::MAX));
+        map.add("b".to_owned());
+
+        let mut loaded = round_trip(&map);
+        assert_eq!(loaded.retired_slot_count(), 1);
+        assert_ne!(loaded.add("c".to_owned()).index(), id.index());
+        loaded.debug_validate();
+    }
+
+    #[test]
+    fn test_serde_preserves_reservations() {
+        let mut map = SlotMap::new();
+        let committed = map.reserve_id();
+        map.commit(committed, "a".to_owned());
+        let flushed = map.reserve_id();
+        map.add("b".to_owned());
+        let pending = map.reserve_id();
+
+        let mut loaded = round_trip(&map);
+        loaded.debug_validate();
+        assert_eq!(loaded.get(committed).map(String::as_str), Some("a"));
+
+        loaded.commit(pending, "c".to_owned());
+        assert!(loaded.cancel_reservation(flushed));
+        assert_eq!(loaded.get(pending).map(String::as_str), Some("c"));
+        loaded.debug_validate();
+    }
+
+    #[test]
+    fn test_serde_id() {
+        let mut map = SlotMap::new();
+        let id = map.add(());
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(serde_json::from_str::<SlotMapId>(&json).unwrap(), id);
+    }
+
+    #[test]
+    fn test_serde_declared_key() {
+        crate::new_key_type! {
+            struct SaveKey;
+            struct CompactSaveKey(crate::CompactSlotMapId);
+        }
+
+        let mut map = SlotMap::<SaveKey, String>::with_key();
+        map.add("a".to_owned());
+        let id = map.add("b".to_owned());
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, serde_json::to_string(&Key::slot_map_id(&id)).unwrap());
+        assert_eq!(serde_json::from_str::<SaveKey>(&json).unwrap(), id);
+
+        let json = serde_json::to_string(&map).unwrap();
+        let loaded = serde_json::from_str::<SlotMap<SaveKey, String>>(&json).unwrap();
+        assert_eq!(loaded.get(id).map(String::as_str), Some("b"));
+
+        let mut map = SlotMap::<CompactSaveKey, ()>::with_key();
+        let id = map.add(());
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(serde_json::from_str::<CompactSaveKey>(&json).unwrap(), id);
+    }
+
+    #[test]
+    fn test_serde_rejects_corrupt_data() {
+        let (map, _, _) = churned_map(SlotMapConfig::default());
+        let mut json = serde_json::to_value(&map).unwrap();
+        json["free_slot_indices"][0] = json["element_slot_indices"][0].clone();
+        assert!(serde_json::from_value::<SlotMap<SlotMapId, String>>(json).is_err());
+
+        let mut json = serde_json::to_value(&map).unwrap();
+        json["free_slot_indices"] = serde_json::Value::Array(vec![]);
+        assert!(serde_json::from_value::<SlotMap<SlotMapId, String>>(json).is_err());
+
+        let mut json = serde_json::to_value(&map).unwrap();
+        json["element_slot_indices"][0] = 10_000.into();
+        assert!(serde_json::from_value::<SlotMap<SlotMapId, String>>(json).is_err());
+    }
+}
@@ -0,0 +1,577 @@
+use crate::{
+    chunked_vec::ChunkedVec,
+    key::{max_generation, max_index},
+    GenerationPolicy, Key, SlotMapId,
+};
+use std::{marker::PhantomData, num::NonZeroU32, ops::Deref};
+
+/// Marks the end of the free list.
+const NIL: u32 = u32::MAX;
+
+/// A slot map that can take `O(1)` snapshots of itself.
+///
+/// Its storage is split into chunks that are shared with the snapshots, and a change only copies
+/// the chunks it writes to, so taking a snapshot every frame and then changing a few entries stays
+/// cheap however large the map is. Reading goes through `Deref` to the current
+/// [`SlotMapSnapshot`]. Freed slots are reused last-in, first-out.
+pub struct CowSlotMap<K, T> {
+    current: SlotMapSnapshot<K, T>,
+    generation_policy: GenerationPolicy,
+}
+
+/// A frozen, read-only view of a [`CowSlotMap`], cheap to clone and to send to other threads.
+pub struct SlotMapSnapshot<K, T> {
+    elements: ChunkedVec<T>,
+    element_slot_indices: ChunkedVec<u32>,
+    /// Holds the next free slot for free slots, like the intrusive free list of
+    /// [`SlotMap`](crate::SlotMap).
+    slot_element_indices: ChunkedVec<u32>,
+    slot_generations: ChunkedVec<NonZeroU32>,
+    free_slot_head: u32,
+    key: PhantomData<fn() -> K>,
+}
+
+impl<T> CowSlotMap<SlotMapId, T> {
+    pub fn new() -> Self {
+        Self::with_key()
+    }
+
+    pub fn with_generation_policy(generation_policy: GenerationPolicy) -> Self {
+        Self::with_key_and_generation_policy(generation_policy)
+    }
+}
+
+impl<K, T> CowSlotMap<K, T>
+where
+    K: Key,
+{
+    pub fn with_key() -> Self {
+        Self::with_key_and_generation_policy(GenerationPolicy::default())
+    }
+
+    pub fn with_key_and_generation_policy(generation_policy: GenerationPolicy) -> Self {
+        Self {
+            current: SlotMapSnapshot {
+                elements: ChunkedVec::new(),
+                element_slot_indices: ChunkedVec::new(),
+                slot_element_indices: ChunkedVec::new(),
+                slot_generations: ChunkedVec::new(),
+                free_slot_head: NIL,
+                key: PhantomData,
+            },
+            generation_policy,
+        }
+    }
+
+    pub fn generation_policy(&self) -> GenerationPolicy {
+        self.generation_policy
+    }
+
+    /// Checks every internal invariant of the map, panicking with a description of the first
+    /// violation found. Intended for tests and debugging; it walks the whole map.
+    pub fn debug_validate(&self) {
+        let allow_retired = self.generation_policy != GenerationPolicy::Wrap;
+        if let Err(err) = self.current.validate(allow_retired) {
+            panic!("{err}");
+        }
+    }
+
+    /// Freezes the current state of the map.
+    pub fn snapshot(&self) -> SlotMapSnapshot<K, T> {
+        self.current.clone()
+    }
+
+    /// Rolls the map back to `snapshot`.
+    ///
+    /// Slot generations roll back as well, so keys handed out after the snapshot was taken can
+    /// be handed out again, exactly as they were the first time.
+    pub fn restore(&mut self, snapshot: &SlotMapSnapshot<K, T>) {
+        self.current = snapshot.clone();
+    }
+}
+
+impl<K, T> CowSlotMap<K, T>
+where
+    K: Key,
+    T: Clone,
+{
+    pub fn get_mut(&mut self, key: K) -> Option<&mut T> {
+        let element_index = self.current.element_index_of(key.slot_map_id())?;
+        self.current.elements.get_mut(element_index)
+    }
+
+    pub fn add(&mut self, item: T) -> K {
+        self.insert_with_key(|_| item)
+    }
+
+    /// Inserts a value built from its own key, for values that need to know their key up front.
+    pub fn insert_with_key(&mut self, f: impl FnOnce(K) -> T) -> K {
+        let map = &mut self.current;
+        let slot_count = map.slot_generations.len();
+
+        let id = if map.free_slot_head != NIL {
+            let generation = map.slot_generations.get(map.free_slot_head as usize);
+            SlotMapId::new(map.free_slot_head, *generation.unwrap())
+        } else {
+            let slot_index = u32::try_from(slot_count)
+                .ok()
+                .filter(|&slot_index| slot_index <= max_index::<K>())
+                .expect("slot map ran out of slot indices");
+            SlotMapId::new(slot_index, NonZeroU32::MIN)
+        };
+
+        let item = f(K::from(id));
+        let element_index = map.elements.len() as u32;
+        map.elements.push(item);
+        map.element_slot_indices.push(id.index());
+
+        match map.slot_element_indices.get_mut(id.index() as usize) {
+            Some(link) => map.free_slot_head = std::mem::replace(link, element_index),
+            None => {
+                map.slot_element_indices.push(element_index);
+                map.slot_generations.push(id.generation());
+            }
+        }
+
+        K::from(id)
+    }
+
+    /// Removes the entry by moving the last element into its place, like
+    /// [`SlotMap::remove`](crate::SlotMap::remove).
+    pub fn remove(&mut self, key: K) -> Option<T> {
+        let id = key.slot_map_id();
+        let map = &mut self.current;
+        let element_index = map.element_index_of(id)?;
+
+        if id.generation() == max_generation::<K>()
+            && self.generation_policy == GenerationPolicy::Panic
+        {
+            panic!("generation of slot {} is exhausted", id.index());
+        }
+
+        Some(self.remove_element(element_index, id))
+    }
+
+    /// Removes the element of `id` without checking the generation policy, retiring an exhausted
+    /// slot under [`GenerationPolicy::Panic`] like the bulk removals of a [`SlotMap`].
+    fn remove_element(&mut self, element_index: usize, id: SlotMapId) -> T {
+        let map = &mut self.current;
+        let exhausted = id.generation() == max_generation::<K>();
+
+        let removed = map.elements.swap_remove(element_index);
+        map.element_slot_indices.swap_remove(element_index);
+
+        if let Some(&moved_slot_index) = map.element_slot_indices.get(element_index) {
+            *map.slot_element_indices
+                .get_mut(moved_slot_index as usize)
+                .unwrap() = element_index as u32;
+        }
+
+        let slot_index = id.index() as usize;
+        let generation = match id.generation().checked_add(1) {
+            Some(generation) if !exhausted => generation,
+            // The slot keeps its exhausted generation and is never marked as free again.
+            _ if self.generation_policy != GenerationPolicy::Wrap => {
+                *map.slot_element_indices.get_mut(slot_index).unwrap() = NIL;
+                return removed;
+            }
+            _ => NonZeroU32::MIN,
+        };
+        *map.slot_generations.get_mut(slot_index).unwrap() = generation;
+        *map.slot_element_indices.get_mut(slot_index).unwrap() = map.free_slot_head;
+        map.free_slot_head = id.index();
+
+        removed
+    }
+
+    pub fn clear(&mut self) {
+        // Removing from the back never moves an element.
+        while let Some(element_index) = self.current.elements.len().checked_sub(1) {
+            let map = &self.current;
+            let slot_index = *map.element_slot_indices.get(element_index).unwrap();
+            let generation = *map.slot_generations.get(slot_index as usize).unwrap();
+            self.remove_element(element_index, SlotMapId::new(slot_index, generation));
+        }
+    }
+}
+
+impl<K, T> SlotMapSnapshot<K, T>
+where
+    K: Key,
+{
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains_key(&self, key: K) -> bool {
+        self.element_index_of(key.slot_map_id()).is_some()
+    }
+
+    pub fn get(&self, key: K) -> Option<&T> {
+        self.elements.get(self.element_index_of(key.slot_map_id())?)
+    }
+
+    /// Iterates in the same dense order a [`SlotMap`](crate::SlotMap) would.
+    pub fn iter(&self) -> impl Iterator<Item = (K, &T)> {
+        self.keys().zip(self.elements.iter())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = K> + '_ {
+        self.element_slot_indices.iter().map(|&slot_index| {
+            let generation = *self.slot_generations.get(slot_index as usize).unwrap();
+            K::from(SlotMapId::new(slot_index, generation))
+        })
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.elements.iter()
+    }
+
+    /// Checks every internal invariant of the snapshot like
+    /// [`CowSlotMap::debug_validate`], accepting retired slots as the snapshot does not know the
+    /// generation policy of its map.
+    pub fn debug_validate(&self) {
+        if let Err(err) = self.validate(true) {
+            panic!("{err}");
+        }
+    }
+
+    fn validate(&self, allow_retired: bool) -> Result<(), String> {
+        if self.elements.len() != self.element_slot_indices.len() {
+            return Err("element count does not match element slot index count".to_owned());
+        }
+
+        if self.slot_element_indices.len() != self.slot_generations.len() {
+            return Err("slot element index count does not match slot generation count".to_owned());
+        }
+
+        let slot_count = self.slot_generations.len();
+        let mut occupied = vec![false; slot_count];
+
+        if slot_count > 0 && slot_count - 1 > max_index::<K>() as usize {
+            return Err(format!(
+                "{slot_count} slots exceed the index width of the key"
+            ));
+        }
+
+        if let Some(slot_index) = self
+            .slot_generations
+            .iter()
+            .position(|&generation| generation > max_generation::<K>())
+        {
+            return Err(format!("generation of slot {slot_index} exceeds the key"));
+        }
+
+        for (element_index, &slot_index) in self.element_slot_indices.iter().enumerate() {
+            let slot_index = slot_index as usize;
+
+            if slot_count <= slot_index {
+                return Err(format!(
+                    "element {element_index} refers to out-of-range slot {slot_index}"
+                ));
+            }
+
+            if occupied[slot_index] {
+                return Err(format!("slot {slot_index} is shared by multiple elements"));
+            }
+
+            if *self.slot_element_indices.get(slot_index).unwrap() as usize != element_index {
+                return Err(format!(
+                    "slot {slot_index} does not point back to element {element_index}"
+                ));
+            }
+
+            occupied[slot_index] = true;
+        }
+
+        let mut is_free = vec![false; slot_count];
+        let mut free_slot_index = self.free_slot_head;
+
+        while free_slot_index != NIL {
+            let slot_index = free_slot_index as usize;
+
+            if slot_count <= slot_index {
+                return Err(format!("out-of-range slot {slot_index} is marked as free"));
+            }
+
+            if occupied[slot_index] {
+                return Err(format!("slot {slot_index} is occupied but marked as free"));
+            }
+
+            // Revisiting a slot means the free list loops.
+            if is_free[slot_index] {
+                return Err(format!(
+                    "slot {slot_index} is marked as free more than once"
+                ));
+            }
+
+            is_free[slot_index] = true;
+            free_slot_index = *self.slot_element_indices.get(slot_index).unwrap();
+        }
+
+        for (slot_index, (&occupied, &is_free)) in occupied.iter().zip(&is_free).enumerate() {
+            if occupied || is_free {
+                continue;
+            }
+
+            if !allow_retired {
+                return Err(format!(
+                    "slot {slot_index} is neither occupied nor marked as free"
+                ));
+            }
+
+            if *self.slot_generations.get(slot_index).unwrap() != max_generation::<K>() {
+                return Err(format!(
+                    "slot {slot_index} is retired before exhausting its generation"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn element_index_of(&self, id: SlotMapId) -> Option<usize> {
+        let slot_index = id.index() as usize;
+
+        if self.slot_generations.get(slot_index) != Some(&id.generation()) {
+            return None;
+        }
+
+        // A freed slot holds a free list link instead, so the back-reference decides occupancy.
+        let element_index = *self.slot_element_indices.get(slot_index)? as usize;
+        if self.element_slot_indices.get(element_index) != Some(&id.index()) {
+            return None;
+        }
+
+        Some(element_index)
+    }
+}
+
+impl<K, T> Default for CowSlotMap<K, T>
+where
+    K: Key,
+{
+    fn default() -> Self {
+        Self::with_key()
+    }
+}
+
+impl<K, T> Deref for CowSlotMap<K, T> {
+    type Target = SlotMapSnapshot<K, T>;
+
+    fn deref(&self) -> &SlotMapSnapshot<K, T> {
+        &self.current
+    }
+}
+
+impl<K, T> Clone for SlotMapSnapshot<K, T> {
+    fn clone(&self) -> Self {
+        Self {
+            elements: self.elements.clone(),
+            element_slot_indices: self.element_slot_indices.clone(),
+            slot_element_indices: self.slot_element_indices.clone(),
+            slot_generations: self.slot_generations.clone(),
+            free_slot_head: self.free_slot_head,
+            key: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunked_vec::CHUNK_LEN, CompactSlotMapId, SlotMap};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_snapshot_is_frozen() {
+        let mut map = CowSlotMap::new();
+        let a = map.add("a".to_owned());
+        let b = map.add("b".to_owned());
+
+        let snapshot = map.snapshot();
+        map.remove(a);
+        map.get_mut(b).unwrap().push('!');
+        let c = map.add("c".to_owned());
+
+        assert_eq!(snapshot.get(a).map(String::as_str), Some("a"));
+        assert_eq!(snapshot.get(b).map(String::as_str), Some("b"));
+        assert_eq!(snapshot.get(c), None);
+        assert_eq!(map.get(a), None);
+        assert_eq!(map.get(b).map(String::as_str), Some("b!"));
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            vec![(b, &"b!".to_owned()), (c, &"c".to_owned())]
+        );
+
+        let render_thread = std::thread::spawn({
+            let snapshot = snapshot.clone();
+            move || snapshot.values().cloned().collect::<Vec<_>>()
+        });
+        assert_eq!(render_thread.join().unwrap(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut map = CowSlotMap::new();
+        let a = map.add(1);
+        let snapshot = map.snapshot();
+
+        let b = map.add(2);
+        map.remove(a);
+        assert_eq!(map.len(), 1);
+
+        map.restore(&snapshot);
+        assert_eq!(map.get(a), Some(&1));
+        assert_eq!(map.get(b), None);
+        assert_eq!(map.add(2), b);
+
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_snapshot_write_copies_one_chunk() {
+        static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+        struct Counted(u32);
+
+        impl Clone for Counted {
+            fn clone(&self) -> Self {
+                CLONES.fetch_add(1, Ordering::Relaxed);
+                Counted(self.0)
+            }
+        }
+
+        let mut map = CowSlotMap::new();
+        let ids = (0..CHUNK_LEN as u32 * 16)
+            .map(|v| map.add(Counted(v)))
+            .collect::<Vec<_>>();
+
+        for frame in 0..10 {
+            let snapshot = map.snapshot();
+            CLONES.store(0, Ordering::Relaxed);
+            map.get_mut(ids[frame * 100]).unwrap().0 += 1;
+
+            assert_eq!(CLONES.load(Ordering::Relaxed), CHUNK_LEN);
+            assert_eq!(map.elements.shared_chunk_count(&snapshot.elements), 15);
+            assert_eq!(
+                map.slot_generations
+                    .shared_chunk_count(&snapshot.slot_generations),
+                16
+            );
+        }
+
+        let snapshot = map.snapshot();
+        map.remove(ids[0]);
+        // The last element moves into the freed position, so two element chunks are copied.
+        assert_eq!(map.elements.shared_chunk_count(&snapshot.elements), 14);
+        assert_eq!(
+            map.slot_generations
+                .shared_chunk_count(&snapshot.slot_generations),
+            15
+        );
+    }
+
+    #[test]
+    fn test_snapshot_matches_slot_map() {
+        let mut map = CowSlotMap::new();
+        let mut reference = SlotMap::new();
+        let mut saved = None;
+        let mut seed = 0x2545_f491_u64;
+
+        for step in 0..20_000u64 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let live = reference.keys().collect::<Vec<_>>();
+
+            match seed % 8 {
+                0..=3 => assert_eq!(map.add(step), reference.add(step)),
+                4 | 5 if !live.is_empty() => {
+                    let id = live[(seed >> 8) as usize % live.len()];
+                    assert_eq!(map.remove(id), reference.remove(id));
+                }
+                6 if !live.is_empty() => {
+                    let id = live[(seed >> 8) as usize % live.len()];
+                    *map.get_mut(id).unwrap() += 1;
+                    *reference.get_mut(id).unwrap() += 1;
+                }
+                7 => match saved.take() {
+                    Some((snapshot, saved_reference)) if seed & 0x100 != 0 => {
+                        map.restore(&snapshot);
+                        reference = saved_reference;
+                    }
+                    _ => saved = Some((map.snapshot(), reference.clone())),
+                },
+                _ => {}
+            }
+
+            assert_eq!(map.len(), reference.len());
+        }
+
+        assert!(map.iter().eq(reference.iter()));
+        map.debug_validate();
+    }
+
+    #[test]
+    fn test_snapshot_slot_reuse_and_generations() {
+        let mut map = CowSlotMap::<CompactSlotMapId, _>::with_key_and_generation_policy(
+            GenerationPolicy::Retire,
+        );
+        let a = map.add(0);
+        let b = map.add(1);
+        map.remove(a);
+        map.remove(b);
+
+        // Freed slots are reused last-in, first-out, with a new generation.
+        let c = map.add(2);
+        assert_eq!((c.index(), c.generation().get()), (1, 2));
+
+        let mut id = map.add(3);
+        assert_eq!(id.index(), 0);
+        while id.generation().get() < u16::MAX {
+            map.remove(id);
+            id = map.add(3);
+            assert_eq!(id.index(), 0);
+        }
+
+        // The exhausted slot is retired instead of wrapping.
+        map.remove(id);
+        assert_eq!(map.add(4).index(), 2);
+        assert_eq!(map.len(), 2);
+
+        map.debug_validate();
+
+        map.clear();
+        assert!(map.is_empty());
+        assert!(!map.contains_key(c));
+        map.debug_validate();
+    }
+
+    #[test]
+    fn test_snapshot_clear_retires_exhausted_slot_under_panic() {
+        let mut map = CowSlotMap::<CompactSlotMapId, _>::with_key_and_generation_policy(
+            GenerationPolicy::Panic,
+        );
+        let mut id = map.add(0);
+        while id.generation().get() < u16::MAX {
+            map.remove(id);
+            id = map.add(0);
+        }
+        map.add(1);
+
+        map.clear();
+        assert!(map.is_empty());
+        assert!(!map.contains_key(id));
+        map.debug_validate();
+
+        // The exhausted slot is never handed out again.
+        assert_ne!(map.add(2).index(), id.index());
+        assert_ne!(map.add(3).index(), id.index());
+    }
+}
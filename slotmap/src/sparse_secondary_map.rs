@@ -0,0 +1,197 @@
+use crate::{key::is_newer_generation, Key, SlotMap, SlotMapId};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    marker::PhantomData,
+    num::NonZeroU32,
+};
+
+/// A [`SecondaryMap`](crate::SecondaryMap) that stores its entries in a hash map, for side data
+/// that only a small fraction of the primary map's ids carry.
+pub struct SparseSecondaryMap<K, V> {
+    entries: HashMap<u32, (NonZeroU32, V)>,
+    key: PhantomData<fn() -> K>,
+}
+
+impl<K, V> SparseSecondaryMap<K, V>
+where
+    K: Key,
+{
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            key: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains_key(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn get(&self, key: K) -> Option<&V> {
+        let id = key.slot_map_id();
+
+        match self.entries.get(&id.index()) {
+            Some((generation, value)) if *generation == id.generation() => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: K) -> Option<&mut V> {
+        let id = key.slot_map_id();
+
+        match self.entries.get_mut(&id.index()) {
+            Some((generation, value)) if *generation == id.generation() => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Inserts a value for `id`, returning the previous value stored for the very same id.
+    ///
+    /// The value is dropped if the slot holds an entry of a newer generation than `id`.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let id = key.slot_map_id();
+
+        match self.entries.entry(id.index()) {
+            Entry::Occupied(mut entry) => {
+                let (generation, stored) = entry.get_mut();
+
+                if *generation == id.generation() {
+                    Some(std::mem::replace(stored, value))
+                } else if is_newer_generation::<K>(id.generation(), *generation) {
+                    entry.insert((id.generation(), value));
+                    None
+                } else {
+                    None
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert((id.generation(), value));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: K) -> Option<V> {
+        let id = key.slot_map_id();
+
+        match self.entries.entry(id.index()) {
+            Entry::Occupied(entry) if entry.get().0 == id.generation() => Some(entry.remove().1),
+            _ => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (K, &V)> {
+        self.entries.iter().map(|(index, (generation, value))| {
+            (K::from(SlotMapId::new(*index, *generation)), value)
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (K, &mut V)> {
+        self.entries.iter_mut().map(|(index, (generation, value))| {
+            (K::from(SlotMapId::new(*index, *generation)), value)
+        })
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(K, &mut V) -> bool) {
+        self.entries.retain(|index, (generation, value)| {
+            f(K::from(SlotMapId::new(*index, *generation)), value)
+        });
+    }
+
+    /// Drops every entry whose id is no longer alive in `primary`.
+    pub fn retain_alive<T>(&mut self, primary: &SlotMap<K, T>) {
+        self.retain(|id, _| primary.get(id).is_some());
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl<K, V> Default for SparseSecondaryMap<K, V>
+where
+    K: Key,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CompactSlotMapId;
+
+    #[test]
+    fn test_sparse_secondary_map_stale_entries() {
+        let mut primary = SlotMap::new();
+        let id1 = primary.add("a");
+
+        let mut secondary = SparseSecondaryMap::new();
+        assert_eq!(secondary.insert(id1, 1), None);
+        assert_eq!(secondary.insert(id1, 2), Some(1));
+
+        primary.remove(id1);
+        let id2 = primary.add("b");
+        assert_eq!(secondary.get(id2), None);
+
+        assert_eq!(secondary.insert(id2, 3), None);
+        assert_eq!(secondary.len(), 1);
+        assert_eq!(secondary.get(id1), None);
+        assert_eq!(secondary.get(id2), Some(&3));
+
+        assert_eq!(secondary.insert(id1, 4), None);
+        assert_eq!(secondary.get(id1), None);
+        assert_eq!(secondary.get(id2), Some(&3));
+        assert_eq!(secondary.remove(id1), None);
+        assert_eq!(secondary.remove(id2), Some(3));
+        assert!(secondary.is_empty());
+    }
+
+    #[test]
+    fn test_sparse_secondary_map_generation_wrap() {
+        let mut primary = SlotMap::<CompactSlotMapId, u32>::with_key();
+        let mut secondary = SparseSecondaryMap::new();
+
+        let mut id = primary.add(0);
+        while id.generation().get() < u16::MAX {
+            primary.remove(id);
+            id = primary.add(0);
+        }
+        secondary.insert(id, "old");
+
+        primary.remove(id);
+        let wrapped = primary.add(0);
+        assert_eq!(wrapped.generation().get(), 1);
+
+        assert_eq!(secondary.insert(wrapped, "new"), None);
+        assert_eq!(secondary.get(wrapped), Some(&"new"));
+        assert_eq!(secondary.get(id), None);
+
+        assert_eq!(secondary.insert(id, "stale"), None);
+        assert_eq!(secondary.get(wrapped), Some(&"new"));
+    }
+
+    #[test]
+    fn test_sparse_secondary_map_retain_alive() {
+        let mut primary = SlotMap::new();
+        let id1 = primary.add("a");
+        let id2 = primary.add("b");
+
+        let mut secondary = SparseSecondaryMap::new();
+        secondary.insert(id1, 1);
+        secondary.insert(id2, 2);
+
+        primary.remove(id2);
+        secondary.retain_alive(&primary);
+        assert_eq!(secondary.iter().collect::<Vec<_>>(), vec![(id1, &1)]);
+    }
+}
@@ -0,0 +1,330 @@
+//! Randomized model-based tests that replay long sequences of operations against both a
+//! `SlotMap` and a `HashMap` reference, validating the map's invariants after every step.
+//! A `CowSlotMap` is cross-checked the same way against a `SlotMap` it mirrors.
+
+use slotmap::{CowSlotMap, FreeSlotPolicy, SlotMap, SlotMapConfig, SlotMapId, SlotMapSnapshot};
+use std::collections::HashMap;
+
+/// Small deterministic xorshift generator so that failures are reproducible from the seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+struct Model {
+    map: SlotMap<SlotMapId, u64>,
+    reference: HashMap<SlotMapId, u64>,
+    live_ids: Vec<SlotMapId>,
+    dead_ids: Vec<SlotMapId>,
+    next_value: u64,
+}
+
+impl Model {
+    fn new(free_slot_policy: FreeSlotPolicy) -> Self {
+        Self {
+            map: SlotMap::with_config(SlotMapConfig {
+                free_slot_policy,
+                ..Default::default()
+            }),
+            reference: HashMap::new(),
+            live_ids: Vec::new(),
+            dead_ids: Vec::new(),
+            next_value: 0,
+        }
+    }
+
+    fn add(&mut self) {
+        let value = self.next_value;
+        self.next_value += 1;
+
+        let id = self.map.add(value);
+        assert!(
+            self.reference.insert(id, value).is_none(),
+            "{id:?} was handed out while still alive"
+        );
+        assert!(!self.dead_ids.contains(&id), "{id:?} resurrected a dead id");
+        self.live_ids.push(id);
+    }
+
+    fn remove_live(&mut self, rng: &mut Rng) {
+        if self.live_ids.is_empty() {
+            return;
+        }
+
+        let id = self.live_ids.swap_remove(rng.below(self.live_ids.len()));
+        let removed = if rng.below(2) == 0 {
+            self.map.remove(id)
+        } else {
+            self.map.remove_stable(id)
+        };
+        assert_eq!(removed, self.reference.remove(&id));
+        assert_eq!(
+            self.map.remove(id),
+            None,
+            "double removal of {id:?} succeeded"
+        );
+        self.dead_ids.push(id);
+    }
+
+    fn remove_dead(&mut self, rng: &mut Rng) {
+        if self.dead_ids.is_empty() {
+            return;
+        }
+
+        let id = self.dead_ids[rng.below(self.dead_ids.len())];
+        assert_eq!(
+            self.map.remove(id),
+            None,
+            "removal of dead {id:?} succeeded"
+        );
+    }
+
+    fn mutate(&mut self, rng: &mut Rng) {
+        if self.live_ids.is_empty() {
+            return;
+        }
+
+        let id = self.live_ids[rng.below(self.live_ids.len())];
+        let value = self.next_value;
+        self.next_value += 1;
+
+        *self.map.get_mut(id).unwrap() = value;
+        *self.reference.get_mut(&id).unwrap() = value;
+    }
+
+    fn retain(&mut self, rng: &mut Rng) {
+        let modulus = rng.below(4) as u64 + 2;
+        self.map.retain(|_, value| !value.is_multiple_of(modulus));
+
+        for id in &self.live_ids {
+            if self.reference[id].is_multiple_of(modulus) {
+                self.reference.remove(id);
+                self.dead_ids.push(*id);
+            }
+        }
+        self.live_ids.retain(|id| self.reference.contains_key(id));
+    }
+
+    fn sort(&mut self) {
+        self.map.sort_by_key(|&value| value);
+
+        let values = self.map.values().copied().collect::<Vec<_>>();
+        assert!(values.is_sorted(), "sort left {values:?} out of order");
+    }
+
+    fn drain(&mut self) {
+        let mut drained = self.map.drain().collect::<Vec<_>>();
+        let mut expected = self.reference.drain().collect::<Vec<_>>();
+        drained.sort();
+        expected.sort();
+        assert_eq!(drained, expected);
+
+        self.dead_ids.append(&mut self.live_ids);
+        self.map.shrink_to_fit();
+    }
+
+    fn check(&self) {
+        self.map.debug_validate();
+        assert_eq!(self.map.len(), self.reference.len());
+
+        for (id, value) in &self.reference {
+            assert_eq!(self.map.get(*id), Some(value), "{id:?} lost its value");
+        }
+
+        for id in &self.dead_ids {
+            assert_eq!(self.map.get(*id), None, "dead {id:?} still resolves");
+        }
+
+        let mut entries = self
+            .map
+            .iter()
+            .map(|(id, value)| (id, *value))
+            .collect::<Vec<_>>();
+        let mut expected = self
+            .reference
+            .iter()
+            .map(|(id, value)| (*id, *value))
+            .collect::<Vec<_>>();
+        entries.sort();
+        expected.sort();
+        assert_eq!(entries, expected);
+    }
+}
+
+fn run(free_slot_policy: FreeSlotPolicy, seed: u64, steps: usize) {
+    let mut rng = Rng::new(seed);
+    let mut model = Model::new(free_slot_policy);
+
+    for _ in 0..steps {
+        match rng.below(100) {
+            0..=44 => model.add(),
+            45..=79 => model.remove_live(&mut rng),
+            80..=87 => model.remove_dead(&mut rng),
+            88..=96 => model.mutate(&mut rng),
+            97 => model.retain(&mut rng),
+            98 => model.sort(),
+            _ => model.drain(),
+        }
+
+        model.check();
+    }
+}
+
+const FREE_SLOT_POLICIES: [FreeSlotPolicy; 2] =
+    [FreeSlotPolicy::Lifo, FreeSlotPolicy::LowestIndexFirst];
+
+#[test]
+fn test_model_random_sequences() {
+    for free_slot_policy in FREE_SLOT_POLICIES {
+        for seed in 0..32 {
+            run(free_slot_policy, seed, 2_000);
+        }
+    }
+}
+
+#[test]
+fn test_model_heavy_churn() {
+    for free_slot_policy in FREE_SLOT_POLICIES {
+        heavy_churn(free_slot_policy);
+    }
+}
+
+fn heavy_churn(free_slot_policy: FreeSlotPolicy) {
+    let mut rng = Rng::new(0xC0FFEE);
+    let mut model = Model::new(free_slot_policy);
+
+    // Keep the population small so that the same slots are reused many times over.
+    for _ in 0..20_000 {
+        if model.live_ids.len() < 8 || rng.below(2) == 0 {
+            model.add();
+        } else {
+            model.remove_live(&mut rng);
+        }
+    }
+
+    model.check();
+}
+
+/// Mirrors every operation of a `CowSlotMap` on a `SlotMap` with the same last-in, first-out slot
+/// reuse, restoring snapshots by swapping in a clone of the mirror taken at the same time.
+struct CowModel {
+    map: CowSlotMap<SlotMapId, u64>,
+    mirror: SlotMap<SlotMapId, u64>,
+    dead_ids: Vec<SlotMapId>,
+    saved: Option<(SlotMapSnapshot<SlotMapId, u64>, SlotMap<SlotMapId, u64>)>,
+    next_value: u64,
+}
+
+impl CowModel {
+    fn new() -> Self {
+        Self {
+            map: CowSlotMap::new(),
+            mirror: SlotMap::new(),
+            dead_ids: Vec::new(),
+            saved: None,
+            next_value: 0,
+        }
+    }
+
+    fn random_live_id(&self, rng: &mut Rng) -> Option<SlotMapId> {
+        let index = rng.below(self.mirror.len().max(1));
+        self.mirror.id_at_dense(index)
+    }
+
+    fn add(&mut self) {
+        let value = self.next_value;
+        self.next_value += 1;
+        assert_eq!(self.map.add(value), self.mirror.add(value));
+    }
+
+    fn remove_live(&mut self, rng: &mut Rng) {
+        if let Some(id) = self.random_live_id(rng) {
+            assert_eq!(self.map.remove(id), self.mirror.remove(id));
+            assert_eq!(
+                self.map.remove(id),
+                None,
+                "double removal of {id:?} succeeded"
+            );
+            self.dead_ids.push(id);
+        }
+    }
+
+    fn remove_dead(&mut self, rng: &mut Rng) {
+        if self.dead_ids.is_empty() {
+            return;
+        }
+
+        let id = self.dead_ids[rng.below(self.dead_ids.len())];
+        assert_eq!(self.map.remove(id), self.mirror.remove(id));
+    }
+
+    fn mutate(&mut self, rng: &mut Rng) {
+        if let Some(id) = self.random_live_id(rng) {
+            *self.map.get_mut(id).unwrap() += 1;
+            *self.mirror.get_mut(id).unwrap() += 1;
+        }
+    }
+
+    fn snapshot_or_restore(&mut self, rng: &mut Rng) {
+        match self.saved.take() {
+            Some((snapshot, mirror)) if rng.below(2) == 0 => {
+                snapshot.debug_validate();
+                self.map.restore(&snapshot);
+                self.mirror = mirror;
+            }
+            _ => self.saved = Some((self.map.snapshot(), self.mirror.clone())),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.dead_ids.extend(self.mirror.keys());
+        self.map.clear();
+        self.mirror.clear();
+    }
+
+    fn check(&self) {
+        self.map.debug_validate();
+        assert_eq!(self.map.len(), self.mirror.len());
+        assert!(self.map.iter().eq(self.mirror.iter()), "iteration diverged");
+
+        // Restoring a snapshot may resurrect dead ids, as long as the mirror agrees.
+        for id in &self.dead_ids {
+            assert_eq!(self.map.get(*id), self.mirror.get(*id), "{id:?} diverged");
+        }
+    }
+}
+
+#[test]
+fn test_model_cow_slot_map() {
+    for seed in 0..16 {
+        let mut rng = Rng::new(seed);
+        let mut model = CowModel::new();
+
+        for _ in 0..2_000 {
+            match rng.below(100) {
+                0..=44 => model.add(),
+                45..=74 => model.remove_live(&mut rng),
+                75..=84 => model.remove_dead(&mut rng),
+                85..=94 => model.mutate(&mut rng),
+                95..=98 => model.snapshot_or_restore(&mut rng),
+                _ => model.clear(),
+            }
+
+            model.check();
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use object_system::{Component, ComponentId, Context, ContextProxy, Controller, ObjectId};
+use object_system::{
+    Component, ComponentId, Context, ContextProxy, Controller, Event, EventHandler, EventMeta,
+    ObjectId,
+};
 use std::any::{type_name, Any};
 
 fn main() {
@@ -30,8 +33,7 @@ fn main() {
 
     let empty_object_id = context.with_proxy(|ctx| {
         // create an empty object to test if the `ctx.find_object_ids_by_component_type` correctly ignores objects that does not satisfy the condition
-        let object_id = ctx.create_object();
-        object_id
+        ctx.create_object()
     });
 
     let (dummy_object_id, dummy_component_id) = context.with_proxy(|ctx| {
@@ -122,9 +124,9 @@ impl Controller for MyController {
         ctx.listen_on_late_update(object_id);
 
         if self.id == 1 {
-            ctx.listen_event("clear-1", object_id);
+            ctx.listen::<Clear1, Self>(object_id);
         } else if self.id == 2 {
-            ctx.listen_event("clear-2", object_id);
+            ctx.listen::<Clear2, Self>(object_id);
         }
     }
 
@@ -142,7 +144,7 @@ impl Controller for MyController {
         println!("[{:?}] Component data: {}", object_id, component.data);
 
         if self.id == 1 && component.data == 3 {
-            ctx.emit_event("clear-2", ());
+            ctx.emit(Clear2);
         }
     }
 
@@ -156,44 +158,58 @@ impl Controller for MyController {
         println!("[{:?}] Component data: {}", object_id, component.data);
 
         if self.id == 2 && component.data == 4 {
-            ctx.emit_event("clear-1", ());
+            ctx.emit(Clear1);
         }
     }
+}
+
+struct Clear1;
+
+impl Event for Clear1 {}
 
+struct Clear2;
+
+impl Event for Clear2 {}
+
+impl EventHandler<Clear1> for MyController {
     fn on_event(
         &mut self,
-        event: &str,
-        _param: &dyn Any,
+        _event: &Clear1,
+        meta: &EventMeta,
         object_id: ObjectId,
         ctx: &mut ContextProxy,
     ) {
-        match event {
-            "clear-1" => {
-                println!("[{:?}] Clear 1", object_id);
-                let object = ctx.find_object_by_id_mut(object_id).unwrap();
-                let component_1 = object
-                    .find_component_by_id_mut::<MyComponent>(self.component_1)
-                    .unwrap();
-                component_1.data = 0;
-                let component_2 = object
-                    .find_component_by_id_mut::<MyComponent>(self.component_2)
-                    .unwrap();
-                component_2.data = 0;
-            }
-            "clear-2" => {
-                println!("[{:?}] Clear 2", object_id);
-                let object = ctx.find_object_by_id_mut(object_id).unwrap();
-                let component = object
-                    .find_component_by_id_mut::<MyComponent>(self.component_2)
-                    .unwrap();
-                component.data = 0;
-                let component_1 = object
-                    .find_component_by_id_mut::<MyComponent>(self.component_1)
-                    .unwrap();
-                component_1.data = 0;
-            }
-            _ => {}
-        }
+        println!("[{:?}] Clear 1 from {:?}", object_id, meta.sender);
+        let object = ctx.find_object_by_id_mut(object_id).unwrap();
+        let component_1 = object
+            .find_component_by_id_mut::<MyComponent>(self.component_1)
+            .unwrap();
+        component_1.data = 0;
+        let component_2 = object
+            .find_component_by_id_mut::<MyComponent>(self.component_2)
+            .unwrap();
+        component_2.data = 0;
+    }
+}
+
+impl EventHandler<Clear2> for MyController {
+    fn on_event(
+        &mut self,
+        _event: &Clear2,
+        _meta: &EventMeta,
+        object_id: ObjectId,
+        ctx: &mut ContextProxy,
+    ) {
+        println!("[{:?}] Clear 2", object_id);
+        let object = ctx.find_object_by_id_mut(object_id).unwrap();
+        let component = object
+            .find_component_by_id_mut::<MyComponent>(self.component_2)
+            .unwrap();
+        component.data = 0;
+        let component_1 = object
+            .find_component_by_id_mut::<MyComponent>(self.component_1)
+            .unwrap();
+        component_1.data = 0;
     }
 }
 